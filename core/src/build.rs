@@ -1,21 +1,71 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
 use std::hash::{Hash, Hasher};
-use std::path::PathBuf;
-use std::time::SystemTime;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
 
-use tracing::{info, instrument};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use tracing::{debug, info, instrument, warn};
 
 use crate::error::{Error, Result};
+use crate::observe::{BuildObserver, Event};
 use crate::oci::Input;
-use crate::{fs, oci, Arch, Artifact};
+use crate::policy::{Policy, Rule};
+use crate::{fs, oci, Arch, Artifact, Owner, Symlink};
 
-#[derive(PartialEq, Eq, Debug)]
+/// Prefixes a base image taken from the local images, instead of a registry,
+/// like "local:quay.io/acme/runtime:v1", or pinned to a manifest digest, like
+/// "local:quay.io/acme/runtime@sha256:...". It's either built by another build
+/// of the batch, or found in the cache. The intermediate image is never pushed.
+pub const LOCAL_BASE: &str = "local:";
+
+/// How far the reproducibility check skews the clock of the second build, so
+/// a time taken from it differs between both.
+const REBUILD_SKEW: Duration = Duration::from_secs(60 * 60);
+
+/// Permission bits of symlinks, which are ignored by the container runtimes.
+const SYMLINK_MODE: u32 = 0o777;
+
+#[derive(PartialEq, Eq, Debug, Clone)]
 pub struct Build {
   pub base: String,
-  pub artifacts: HashSet<Artifact>,
+  pub artifacts: Vec<Artifact>,
   pub image: ImageName,
   pub arch: HashSet<Arch>,
+  /// A fixed creation time, making the build reproducible.
+  pub timestamp: Option<SystemTime>,
+  pub config: oci::ConfigChanges,
+  /// Symbolic links to create within the image.
+  pub symlinks: Vec<Symlink>,
+  pub layering: Layering,
+  /// The format of a bill of materials to record along the image, if any.
+  pub sbom: Option<Sbom>,
+}
+
+/// Formats of the software bill of materials.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash)]
+pub enum Sbom {
+  /// SPDX 2.3, in JSON.
+  Spdx,
+  /// CycloneDX 1.5, in JSON.
+  CycloneDx,
+}
+
+/// How the artifacts, not naming their layer, are split into layers. Layers
+/// are stacked in the order their artifacts are first given, so rarely
+/// changing ones should come first.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default, Hash)]
+pub enum Layering {
+  /// All artifacts share a single layer.
+  #[default]
+  Single,
+  /// Each artifact gets a layer of its own. Symlinks share one, on top.
+  PerArtifact,
 }
 
 #[derive(PartialEq, Eq, Debug, Clone)]
@@ -28,6 +78,8 @@ pub struct ImageName {
 pub struct Command {
   pub fs: FileSystem,
   pub oci: Oci,
+  /// Receives the events of the builds, when given.
+  pub observer: Option<Box<dyn BuildObserver>>,
 }
 
 #[derive(Debug)]
@@ -40,6 +92,14 @@ pub struct FileSystem {
 pub struct Oci {
   pub registry: Box<dyn oci::Registry>,
   pub cache: Box<dyn oci::Cache>,
+  /// Signs the built images, when given.
+  pub signer: Option<Box<dyn oci::Signer>>,
+  /// Verifies the signatures of the base images, when given. Bases without
+  /// a valid signature are refused, while the local ones are trusted.
+  pub verifier: Option<Box<dyn oci::Verifier>>,
+  /// The trust policy the base images are checked against. Local ones are
+  /// trusted.
+  pub policy: Policy,
 }
 
 #[derive(PartialEq, Eq, Debug)]
@@ -47,16 +107,81 @@ struct Payload {
   parts: Vec<Part>,
 }
 
-#[derive(PartialEq, Eq, Debug)]
+#[derive(PartialEq, Eq, Debug, Clone)]
 struct Part {
+  /// An index of the layer, counting from the bottom one.
+  layer: usize,
   arch: Option<Arch>,
+  /// The file on host. Empty, for a symlink created within the image.
   from: PathBuf,
-  to: Option<String>,
+  to: String,
+  mode: u32,
+  owner: Option<Owner>,
+  /// The target, when the part is a symlink.
+  symlink: Option<String>,
 }
 
 #[derive(PartialEq, Eq, Debug)]
 pub struct ImageInfo {
   pub digest: String,
+  pub fingerprint: String,
+}
+
+/// What a build would do, resolved without contacting any registry.
+#[derive(PartialEq, Eq, Debug)]
+pub struct Plan {
+  pub base: String,
+  pub image: ImageName,
+  /// Architectures to build for. Empty, when they are to be taken from the
+  /// base image.
+  pub archs: Vec<Arch>,
+  /// Files to add, sorted by layer and destination. Files are listed once per
+  /// given architecture, or once with no architecture, if none were given.
+  pub files: Vec<PlannedFile>,
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub struct PlannedFile {
+  /// An index of the layer, counting from the bottom one.
+  pub layer: usize,
+  pub arch: Option<Arch>,
+  pub from: PathBuf,
+  pub to: String,
+  pub mode: u32,
+  pub owner: Option<Owner>,
+  pub size: u64,
+  /// The target, when the file is a symlink.
+  pub symlink: Option<String>,
+}
+
+/// What an image is built from, to attest its provenance.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Provenance {
+  /// The build, as invoked.
+  pub build: Build,
+  /// The digest of the base image manifest.
+  pub base_digest: String,
+  /// Host files added to the image, in the order they were added.
+  pub materials: Vec<Material>,
+  pub fingerprint: String,
+}
+
+/// A host file an image is built from.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Material {
+  pub path: PathBuf,
+  /// A digest of the file content, like "sha256:...".
+  pub digest: String,
+  /// An absolute path of the file within the image.
+  pub to: String,
+  /// Size of the file, in bytes.
+  pub size: u64,
+  /// A SHA-1 digest of the file content, as hex, which SPDX requires. Only
+  /// computed for a bill of materials.
+  pub sha1: Option<String>,
+  /// Rust crates compiled into the file. Only looked up for a bill of
+  /// materials.
+  pub packages: Vec<fs::Package>,
 }
 
 #[derive(PartialEq, Eq, Debug)]
@@ -68,43 +193,470 @@ pub enum Built {
 impl Command {
   #[instrument(ret, level = "trace")]
   pub fn execute(&self, b: &Build) -> Result<Built> {
+    self.execute_with(b, &Bases::default())
+  }
+
+  /// Builds the images concurrently, running at most `jobs` builds at once.
+  /// The base images shared by builds are fetched once. A build with a local
+  /// base, built by another one of the batch, waits for it, and takes its
+  /// image as is. A failed build doesn't stop the others, but the ones built
+  /// on top of it. Results are given in the order of the builds.
+  #[instrument(skip(builds), level = "trace")]
+  pub fn execute_all(
+    &self,
+    builds: &[Build],
+    jobs: usize,
+  ) -> Vec<Result<Built>> {
+    let bases = Bases::default();
+    let parents: Vec<Vec<usize>> = builds
+      .iter()
+      .map(|b| {
+        let local = b.base.strip_prefix(LOCAL_BASE);
+        (0..builds.len())
+          .filter(|j| local.is_some_and(|l| names(l, &builds[*j].image)))
+          .collect()
+      })
+      .collect();
+    let mut results: Vec<Option<Result<Built>>> =
+      builds.iter().map(|_| None).collect();
+    loop {
+      let mut ready = Vec::new();
+      for (i, parents) in parents.iter().enumerate() {
+        if results[i].is_some() || parents.iter().any(|j| results[*j].is_none())
+        {
+          continue;
+        }
+        if parents.iter().any(|j| matches!(results[*j], Some(Err(_)))) {
+          results[i] = Some(Err(Error::invalid_input(&format!(
+            "base image {} failed to build",
+            builds[i].base
+          ))));
+        } else {
+          ready.push(i);
+        }
+      }
+      if ready.is_empty() && results.iter().all(Option::is_some) {
+        break;
+      }
+      if ready.is_empty() {
+        // the rest waits on each other
+        for (i, res) in results.iter_mut().enumerate() {
+          if res.is_none() {
+            *res = Some(Err(Error::invalid_input(&format!(
+              "base image {} of {} is built on top of it, in a cycle",
+              builds[i].base, builds[i].image.image
+            ))));
+          }
+        }
+        break;
+      }
+      for (i, res) in self.execute_many(builds, &ready, jobs, &bases) {
+        results[i] = Some(res);
+      }
+    }
+    results.into_iter().flatten().collect()
+  }
+
+  /// Builds the chosen builds concurrently, giving results by their indices.
+  fn execute_many(
+    &self,
+    builds: &[Build],
+    chosen: &[usize],
+    jobs: usize,
+    bases: &Bases,
+  ) -> Vec<(usize, Result<Built>)> {
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|scope| {
+      for _ in 0..jobs.clamp(1, chosen.len().max(1)) {
+        let sender = sender.clone();
+        let next = &next;
+        scope.spawn(move || {
+          while let Some(&i) = chosen.get(next.fetch_add(1, Ordering::Relaxed))
+          {
+            debug!(image = ?builds[i].image.image, "Building...");
+            // the receiver outlives the scope, so sending can't fail
+            let _ = sender.send((i, self.execute_with(&builds[i], bases)));
+          }
+        });
+      }
+    });
+    drop(sender);
+    receiver.iter().collect()
+  }
+
+  fn execute_with(&self, b: &Build, bases: &Bases) -> Result<Built> {
+    let settings = self.settings(b, bases)?;
     let payload = self.construct_payload(b)?;
-    let already_built = self.lookup_built(&payload, &b.image)?;
-    if already_built.is_some() {
-      let im = already_built.unwrap();
+    let digests = self.digests(&payload)?;
+    let base_digest = bases.digest(&self.oci, &b.base)?;
+    let fingerprint =
+      payload_fingerprint(&base_digest, &settings, &payload, &digests);
+    debug!(fingerprint = ?fingerprint, "Build fingerprint computed");
+    let provenance = Provenance {
+      build: b.clone(),
+      base_digest,
+      materials: self.materials(&payload, &digests, b.sbom.is_some())?,
+      fingerprint: fingerprint.clone(),
+    };
+    let already_built = self.lookup_built(&fingerprint, &b.image)?;
+    if let Some(im) = already_built {
+      self.notify(
+        b,
+        Event::CacheHit {
+          digest: im.digest(),
+          fingerprint: fingerprint.clone(),
+        },
+      );
+      if b.sbom.is_some() {
+        // the cached image may have been built without a bill of materials
+        im.attest(&provenance)?;
+      }
+      self.sign(b, &*im)?;
+      let digest = im.digest();
+      bases.built(Arc::from(im));
       return Ok(Built::Cached(ImageInfo {
-        digest: im.digest(),
+        digest,
+        fingerprint,
       }));
     }
-    let base = self.oci.registry.fetch(&b.base)?;
-    info!(digest = ?base.digest(), "Base image fetched");
-    let inputs = self.open_payload(payload)?;
-    let constr = base.construct_new(&b.arch);
-    constr.add(inputs);
-    let built = constr.build(&b.image)?;
-
+    let base = self.fetch_base(b, &settings, bases)?;
+    let layers = self.open_payload(payload)?;
+    let built = self.construct(b, &settings, &*base, layers, &fingerprint)?;
+    built.attest(&provenance)?;
+    self.sign(b, &*built)?;
+    let digest = built.digest();
+    bases.built(Arc::from(built));
     Ok(Built::Real(ImageInfo {
-      digest: built.digest(),
+      digest,
+      fingerprint,
     }))
   }
 
-  fn open_payload(&self, payload: Payload) -> Result<Vec<Input>> {
+  /// Passes the event of the build to the observer, if there's one.
+  fn notify(&self, b: &Build, event: Event) {
+    if let Some(observer) = &self.observer {
+      observer.observe(&b.image, &event);
+    }
+  }
+
+  fn fetch_base(
+    &self,
+    b: &Build,
+    settings: &oci::Settings,
+    bases: &Bases,
+  ) -> Result<Arc<dyn oci::Image>> {
+    let progress = |event| self.notify(b, event);
+    bases.fetch(&self.oci, &b.base, &settings.archs, &progress)
+  }
+
+  /// Signs the image, if there's a signer. The signature is kept next to the
+  /// image, so a cached one gets signed too.
+  fn sign(&self, b: &Build, image: &dyn oci::Image) -> Result<()> {
+    if let Some(signer) = &self.oci.signer {
+      image.sign(&signer.sign(&b.image.image, &image.digest())?)?;
+    }
+    Ok(())
+  }
+
+  /// Builds the image twice, bypassing the cache, and checks both builds
+  /// yield the same digest. The second build varies what shouldn't matter:
+  /// its clock is skewed, and it takes the files of each layer in reverse
+  /// order. The build needs a timestamp, as the current time is taken
+  /// otherwise.
+  #[instrument(ret, level = "trace")]
+  pub fn verify_reproducible(&self, b: &Build) -> Result<ImageInfo> {
+    if b.timestamp.is_none() {
+      return Err(Error::invalid_input(&format!(
+        "image {} can't be reproducible without a timestamp",
+        b.image.image
+      )));
+    }
+    let bases = Bases::default();
+    let settings = self.settings(b, &bases)?;
+    let payload = self.construct_payload(b)?;
+    let digests = self.digests(&payload)?;
+    let base_digest = bases.digest(&self.oci, &b.base)?;
+    let fingerprint =
+      payload_fingerprint(&base_digest, &settings, &payload, &digests);
+    let base = self.fetch_base(b, &settings, &bases)?;
+    let layers = self.open_payload(payload)?;
+    let first = self
+      .construct(b, &settings, &*base, layers, &fingerprint)?
+      .digest();
+    let skewed = oci::Settings {
+      clock: oci::Clock { skew: REBUILD_SKEW },
+      ..settings.clone()
+    };
+    let mut layers = self.open_payload(self.construct_payload(b)?)?;
+    for inputs in &mut layers {
+      inputs.reverse();
+    }
+    let second = self
+      .construct(b, &skewed, &*base, layers, &fingerprint)?
+      .digest();
+    if first != second {
+      return Err(Error::NotReproducible { first, second });
+    }
+    Ok(ImageInfo {
+      digest: first,
+      fingerprint,
+    })
+  }
+
+  fn construct(
+    &self,
+    b: &Build,
+    settings: &oci::Settings,
+    base: &dyn oci::Image,
+    layers: Vec<Vec<Input>>,
+    fingerprint: &str,
+  ) -> Result<Box<dyn oci::Image>> {
+    let mut constr = base.construct_new(settings);
+    for inputs in layers {
+      for layer in constr.add(inputs)? {
+        self.notify(
+          b,
+          Event::LayerWritten {
+            arch: layer.arch,
+            digest: layer.digest,
+            size: layer.size,
+          },
+        );
+      }
+    }
+    let built = constr.build(&b.image, fingerprint)?;
+    info!(digest = ?built.digest(), "Image constructed");
+    self.notify(
+      b,
+      Event::ManifestBuilt {
+        digest: built.digest(),
+      },
+    );
+    Ok(built)
+  }
+
+  /// Computes a fingerprint of the build, without building it.
+  ///
+  /// The fingerprint is a sha256 digest over the canonical form of the build
+  /// inputs: the base image digest, the architectures, and the content and
+  /// destination of every artifact file. Two builds with the same
+  /// fingerprint produce the same image content.
+  #[instrument(ret, level = "trace")]
+  pub fn fingerprint(&self, b: &Build) -> Result<String> {
+    let bases = Bases::default();
+    let settings = self.settings(b, &bases)?;
+    let payload = self.construct_payload(b)?;
+    let digests = self.digests(&payload)?;
+    let base_digest = bases.digest(&self.oci, &b.base)?;
+    Ok(payload_fingerprint(
+      &base_digest,
+      &settings,
+      &payload,
+      &digests,
+    ))
+  }
+
+  /// Resolves the artifacts and maps them onto the image, without building it
+  /// or contacting any registry.
+  #[instrument(ret, level = "trace")]
+  pub fn plan(&self, b: &Build) -> Result<Plan> {
+    let mut archs: Vec<Arch> = b.arch.iter().cloned().collect();
+    archs.sort();
+    let payload = self.construct_payload(b)?;
     let mut files = Vec::new();
     for part in payload.parts {
-      let input = self.fs.files.read(&part.from).map(|read| Input {
-        arch: part.arch.clone(),
-        from: read,
+      let size = match part.symlink {
+        Some(_) => 0,
+        None => self.fs.files.size(&part.from)?,
+      };
+      let targets = match &part.arch {
+        Some(arch) if archs.is_empty() || archs.contains(arch) => {
+          vec![Some(arch.clone())]
+        }
+        Some(_) => vec![],
+        None if archs.is_empty() => vec![None],
+        None => archs.iter().cloned().map(Some).collect(),
+      };
+      for arch in targets {
+        files.push(PlannedFile {
+          layer: part.layer,
+          arch,
+          from: part.from.clone(),
+          to: part.to.clone(),
+          mode: part.mode,
+          owner: part.owner.clone(),
+          size,
+          symlink: part.symlink.clone(),
+        });
+      }
+    }
+    files.sort_by(|a, b| {
+      (a.layer, &a.to, &a.arch).cmp(&(b.layer, &b.to, &b.arch))
+    });
+    Ok(Plan {
+      base: b.base.clone(),
+      image: b.image.clone(),
+      archs,
+      files,
+    })
+  }
+
+  fn settings(&self, b: &Build, bases: &Bases) -> Result<oci::Settings> {
+    Ok(oci::Settings {
+      archs: self.archs(b, bases)?,
+      created: b.timestamp,
+      config: b.config.clone(),
+      clock: oci::Clock::default(),
+    })
+  }
+
+  /// Architectures to build for. When none are given, the ones offered by
+  /// the base image are used.
+  fn archs(&self, b: &Build, bases: &Bases) -> Result<HashSet<Arch>> {
+    if !b.arch.is_empty() {
+      return Ok(b.arch.clone());
+    }
+    let archs = bases.platforms(&self.oci, &b.base)?;
+    debug!(archs = ?archs, "Architectures taken from base image");
+    for artifact in &b.artifacts {
+      if let Some(arch) = &artifact.arch {
+        if !archs.contains(arch) {
+          return Err(Error::invalid_input(
+            format!(
+              "artifact {} targets {} platform, that base image {} \
+              doesn't offer",
+              artifact.from, arch, b.base
+            )
+            .as_str(),
+          ));
+        }
+      }
+    }
+    Ok(archs)
+  }
+
+  /// Digests the content of the payload files, in their order. Symlinks are
+  /// digested by their target.
+  fn digests(&self, payload: &Payload) -> Result<Vec<String>> {
+    let mut digests = Vec::new();
+    for part in &payload.parts {
+      digests.push(match &part.symlink {
+        Some(target) => format!("symlink:{}", target),
+        None => {
+          let mut hasher = Sha256::new();
+          io::copy(&mut self.fs.files.read(&part.from)?, &mut hasher)?;
+          format!("sha256:{:x}", hasher.finalize())
+        }
+      });
+    }
+    Ok(digests)
+  }
+
+  /// The host files of the payload, as the materials the image is built
+  /// from. Their SHA-1 digests, and the crates compiled into them, are looked
+  /// up only for a bill of materials.
+  fn materials(
+    &self,
+    payload: &Payload,
+    digests: &[String],
+    sbom: bool,
+  ) -> Result<Vec<Material>> {
+    let mut materials = Vec::new();
+    for (part, digest) in payload.parts.iter().zip(digests) {
+      if part.symlink.is_some() {
+        continue;
+      }
+      materials.push(Material {
+        path: part.from.clone(),
+        digest: digest.clone(),
         to: part.to.clone(),
-      })?;
-      files.push(input);
+        size: self.fs.files.size(&part.from)?,
+        sha1: if sbom {
+          let mut hasher = Sha1::new();
+          io::copy(&mut self.fs.files.read(&part.from)?, &mut hasher)?;
+          Some(format!("{:x}", hasher.finalize()))
+        } else {
+          None
+        },
+        packages: if sbom {
+          self.packages(&part.from)
+        } else {
+          Vec::new()
+        },
+      });
+    }
+    Ok(materials)
+  }
+
+  /// The crates compiled into the host file. A file they can't be read from
+  /// is recorded without them, rather than failing the build.
+  fn packages(&self, path: &Path) -> Vec<fs::Package> {
+    self.fs.files.packages(path).unwrap_or_else(|err| {
+      warn!(path = ?path, error = %err, "Crates of the file unreadable");
+      Vec::new()
+    })
+  }
+
+  /// Opens the payload files, grouped by layers, in the order of their
+  /// destinations. A file sharing the host inode with a previous one of the
+  /// same layer becomes a hard link to it.
+  fn open_payload(&self, mut payload: Payload) -> Result<Vec<Vec<Input>>> {
+    payload
+      .parts
+      .sort_by(|a, b| (a.layer, &a.to).cmp(&(b.layer, &b.to)));
+    let mut stored: HashMap<(u64, u64), Vec<Part>> = HashMap::new();
+    let mut layers: Vec<Vec<Input>> = Vec::new();
+    for part in payload.parts {
+      if layers.len() <= part.layer {
+        layers.resize_with(part.layer + 1, Vec::new);
+        stored.clear();
+      }
+      let link = match &part.symlink {
+        Some(target) => Some(oci::Link::Symbolic(target.clone())),
+        None => match self.fs.files.inode(&part.from)? {
+          None => None,
+          Some(inode) => {
+            let seen = stored.entry(inode).or_default();
+            let link = seen
+              .iter()
+              .find(|first| links_to(&part, first))
+              .map(|first| oci::Link::Hard(first.to.clone()));
+            if link.is_none() {
+              seen.push(part.clone());
+            }
+            link
+          }
+        },
+      };
+      let from: Box<dyn io::Read> = match link {
+        Some(_) => Box::new(io::empty()),
+        None => self.fs.files.read(&part.from)?,
+      };
+      layers[part.layer].push(Input {
+        arch: part.arch,
+        from,
+        to: part.to,
+        mode: part.mode,
+        owner: part.owner,
+        link,
+      });
     }
-    Ok(files)
+    Ok(layers)
   }
 
   #[instrument(ret, level = "trace")]
   fn construct_payload(&self, b: &Build) -> Result<Payload> {
+    let mut layers = Layers::default();
     let mut parts = Vec::new();
-    for artifact in &b.artifacts {
+    for (index, artifact) in b.artifacts.iter().enumerate() {
+      let layer = match (&artifact.layer, b.layering) {
+        (Some(name), _) => layers.index(LayerKey::Named(name.clone())),
+        (None, Layering::Single) => layers.index(LayerKey::Shared),
+        (None, Layering::PerArtifact) => {
+          layers.index(LayerKey::Artifact(index))
+        }
+      };
       let maybe_paths = self.fs.resolver.resolve(artifact);
       match maybe_paths {
         Ok(paths) => {
@@ -113,60 +665,466 @@ impl Command {
               format!("no artifact found: {}", artifact.from).as_str(),
             ));
           }
-          paths
-            .iter()
-            .map(|p| Part {
+          self.notify(
+            b,
+            Event::ArtifactResolved {
+              from: artifact.from.clone(),
+              files: paths.len(),
+            },
+          );
+          let many = paths.len() > 1;
+          for file in paths {
+            let to = destination(&artifact.to, &file, many)?;
+            let symlink = if artifact.keep_symlinks {
+              self.fs.files.symlink(&file.path)?
+            } else {
+              None
+            };
+            let mode = match (&symlink, artifact.mode) {
+              (Some(_), _) => SYMLINK_MODE,
+              (None, Some(mode)) => mode,
+              (None, None) => self.fs.files.mode(&file.path)?,
+            };
+            parts.push(Part {
+              layer,
               arch: artifact.arch.clone(),
-              from: p.to_owned(),
-              to: artifact.to.clone(),
-            })
-            .for_each(|p| parts.push(p))
+              from: file.path,
+              to,
+              mode,
+              owner: artifact.owner.clone(),
+              symlink: symlink.map(|t| t.to_string_lossy().to_string()),
+            });
+          }
         }
         Err(err) => return Err(err),
       };
     }
+    for link in &b.symlinks {
+      parts.push(Part {
+        layer: layers.index(LayerKey::Shared),
+        arch: link.arch.clone(),
+        from: PathBuf::new(),
+        to: image_path(Path::new(&link.path)),
+        mode: SYMLINK_MODE,
+        owner: None,
+        symlink: Some(link.target.clone()),
+      });
+    }
     Ok(Payload { parts })
   }
 
+  /// Finds the image built before of the same name and fingerprint, within
+  /// the cache, tagged with the tags wanted, among others. A hit relies on
+  /// the cache listing the images it persists, with the fingerprints of their
+  /// builds.
   #[instrument(ret, level = "trace")]
   fn lookup_built(
     &self,
-    payload: &Payload,
+    fingerprint: &str,
     im: &ImageName,
   ) -> Result<Option<Box<dyn oci::Image>>> {
-    let images = self.oci.cache.list()?;
+    let images = self.oci.cache.find(&im.image, None)?;
     for image in images {
-      if image.name() == *im {
-        let mt = self.payload_modtime(payload)?;
-        if image.created() >= mt {
-          return Ok(Some(image));
-        }
+      let name = image.name();
+      if name.image == im.image
+        && im.tags.is_subset(&name.tags)
+        && image.fingerprint().as_deref() == Some(fingerprint)
+      {
+        return Ok(Some(image));
       }
     }
     Ok(None)
   }
+}
 
-  fn payload_modtime(&self, payload: &Payload) -> Result<SystemTime> {
-    let mut newest = SystemTime::UNIX_EPOCH;
-    for part in &payload.parts {
-      let mt = self.fs.files.modified(&part.from)?;
-      if mt > newest {
-        newest = mt;
+/// Base images fetched by the builds, so the ones sharing a base image fetch
+/// it once, and images built by them, to serve as local bases of others.
+/// Concurrent builds wait for the ongoing fetch of their base.
+#[derive(Default)]
+struct Bases {
+  fetched: Mutex<HashMap<BaseKey, BaseSlot>>,
+  built: Mutex<Vec<Arc<dyn oci::Image>>>,
+  /// Digests of the base images passing the policy and verified, by their
+  /// names.
+  verified: Mutex<HashMap<String, String>>,
+}
+
+/// A base image name, with the sorted architectures it's fetched for.
+type BaseKey = (String, Vec<Arch>);
+
+/// A base image, once fetched by one of the builds.
+type BaseSlot = Arc<Mutex<Option<Arc<dyn oci::Image>>>>;
+
+impl Bases {
+  fn fetch(
+    &self,
+    oci: &Oci,
+    image: &str,
+    archs: &HashSet<Arch>,
+    notify: &(dyn Fn(Event) + Sync),
+  ) -> Result<Arc<dyn oci::Image>> {
+    if let Some(local) = image.strip_prefix(LOCAL_BASE) {
+      let base = self.local(oci, local)?;
+      let offered = base.platforms()?;
+      if let Some(arch) = archs.iter().find(|a| !offered.contains(a)) {
+        return Err(Error::invalid_input(&format!(
+          "base image {} has no {} platform",
+          image, arch
+        )));
+      }
+      return Ok(base);
+    }
+    let mut key =
+      (image.to_string(), archs.iter().cloned().collect::<Vec<_>>());
+    key.1.sort();
+    let slot = self.fetched.lock().unwrap().entry(key).or_default().clone();
+    let mut slot = slot.lock().unwrap();
+    if let Some(base) = &*slot {
+      debug!(digest = ?base.digest(), "Base image already fetched");
+      return Ok(base.clone());
+    }
+    // pins the verified digest, so a retagged base isn't fetched instead
+    let digest = self.verify(oci, image)?;
+    notify(Event::BaseFetchStarted {
+      image: image.to_string(),
+    });
+    let progress = |pulled, total| {
+      notify(Event::BaseFetchProgress {
+        image: image.to_string(),
+        pulled,
+        total,
+      })
+    };
+    let base: Arc<dyn oci::Image> = Arc::from(oci.registry.fetch(
+      &pinned(image, &digest),
+      archs,
+      &progress,
+    )?);
+    // the registry may serve other content than the verified digest names
+    let fetched = base.fetched_digest();
+    if bare(&fetched) != bare(&digest) {
+      return Err(Error::invalid_input(&format!(
+        "base image {} fetched is {}, instead of the verified sha256:{}",
+        image, fetched, digest
+      )));
+    }
+    info!(digest = ?base.digest(), "Base image fetched");
+    notify(Event::BaseFetchFinished {
+      image: image.to_string(),
+      digest: base.digest(),
+    });
+    *slot = Some(base.clone());
+    Ok(base)
+  }
+
+  fn platforms(&self, oci: &Oci, image: &str) -> Result<HashSet<Arch>> {
+    match image.strip_prefix(LOCAL_BASE) {
+      Some(local) => self.local(oci, local)?.platforms(),
+      None => {
+        oci.policy.check_base(image)?;
+        oci.registry.platforms(image)
+      }
+    }
+  }
+
+  fn digest(&self, oci: &Oci, image: &str) -> Result<String> {
+    match image.strip_prefix(LOCAL_BASE) {
+      Some(local) => Ok(self.local(oci, local)?.digest()),
+      None => self.verify(oci, image),
+    }
+  }
+
+  /// Resolves the digest of the base image, once it passes the policy, and
+  /// checks its signatures verify, when required. Each base is verified once.
+  fn verify(&self, oci: &Oci, image: &str) -> Result<String> {
+    if let Some(digest) = self.verified.lock().unwrap().get(image) {
+      return Ok(digest.clone());
+    }
+    oci.policy.check_base(image)?;
+    let digest = oci.registry.digest(image)?;
+    let scope = oci.policy.signed_scope(image);
+    if oci.verifier.is_some() || scope.is_some() {
+      let signatures = oci.registry.signatures(image, &digest)?;
+      if let Some(verifier) = &oci.verifier {
+        if !signatures.iter().any(|s| verifier.verify(&digest, s)) {
+          return Err(Error::UnverifiedBase {
+            image: image.to_string(),
+            digest,
+          });
+        }
+      }
+      if let Some(scope) = scope {
+        if !scope.verifies(&digest, &signatures) {
+          return Err(Error::PolicyViolation {
+            image: image.to_string(),
+            rule: Rule::Signed(scope.scope.clone()),
+          });
+        }
+      }
+      info!(digest = ?digest, "Base image signature verified");
+    }
+    self
+      .verified
+      .lock()
+      .unwrap()
+      .insert(image.to_string(), digest.clone());
+    Ok(digest)
+  }
+
+  /// Finds a local image: one built before by a build of the batch, or the
+  /// latest one within the cache. A pinned one is found by its digest.
+  fn local(&self, oci: &Oci, reference: &str) -> Result<Arc<dyn oci::Image>> {
+    if let Some((image, digest)) = reference.split_once('@') {
+      return self.local_pinned(oci, image, digest);
+    }
+    let built = self.built.lock().unwrap();
+    if let Some(image) =
+      built.iter().rev().find(|i| names(reference, &i.name()))
+    {
+      return Ok(image.clone());
+    }
+    drop(built);
+    let (image, tag) = split_tag(reference);
+    let cached = oci
+      .cache
+      .find(image, tag)?
+      .into_iter()
+      .max_by_key(|image| image.created());
+    match cached {
+      Some(image) => {
+        debug!(digest = ?image.digest(), "Local base image found in cache");
+        Ok(Arc::from(image))
       }
+      None => Err(Error::invalid_input(&format!(
+        "no local base image {}, build it first",
+        reference
+      ))),
     }
-    if newest == SystemTime::UNIX_EPOCH {
-      return Err(Error::Bug(
-        concat!(
-          "possible bug. no modification date ",
-          "for any artifact was found"
-        )
-        .to_string(),
-      ));
+  }
+
+  fn local_pinned(
+    &self,
+    oci: &Oci,
+    image: &str,
+    digest: &str,
+  ) -> Result<Arc<dyn oci::Image>> {
+    let matches = |i: &dyn oci::Image| {
+      i.name().image == image && bare(&i.digest()) == bare(digest)
+    };
+    let built = self.built.lock().unwrap();
+    if let Some(found) = built.iter().find(|i| matches(i.as_ref())) {
+      return Ok(found.clone());
     }
-    Ok(newest)
+    drop(built);
+    match oci.cache.get(digest)?.filter(|i| matches(i.as_ref())) {
+      Some(found) => {
+        debug!(digest = ?found.digest(), "Local base image found in cache");
+        Ok(Arc::from(found))
+      }
+      None => Err(Error::invalid_input(&format!(
+        "no local base image {}@{}, build it first",
+        image, digest
+      ))),
+    }
+  }
+
+  fn built(&self, image: Arc<dyn oci::Image>) {
+    self.built.lock().unwrap().push(image);
   }
 }
 
+/// The image reference, pinned to the digest.
+fn pinned(image: &str, digest: &str) -> String {
+  match image.split_once('@') {
+    Some((name, _)) => format!("{}@sha256:{}", name, digest),
+    None => format!("{}@sha256:{}", image, digest),
+  }
+}
+
+/// Whether the local image reference, like "quay.io/acme/runtime:v1", names
+/// the image. With no tag given, any tag matches.
+fn names(reference: &str, name: &ImageName) -> bool {
+  let (image, tag) = split_tag(reference);
+  image == name.image && tag.is_none_or(|t| name.tags.contains(t))
+}
+
+/// Splits the image reference into the image name, and its tag, if any.
+fn split_tag(reference: &str) -> (&str, Option<&str>) {
+  match reference.rsplit_once(':') {
+    Some((image, tag)) if !tag.contains('/') => (image, Some(tag)),
+    _ => (reference, None),
+  }
+}
+
+/// The digest, without its algorithm.
+fn bare(digest: &str) -> &str {
+  digest.strip_prefix("sha256:").unwrap_or(digest)
+}
+
+/// Identifies a layer the artifacts are assigned to.
+#[derive(PartialEq, Eq, Debug)]
+enum LayerKey {
+  Shared,
+  Named(String),
+  Artifact(usize),
+}
+
+/// Layers, in the order they are first assigned to.
+#[derive(Default)]
+struct Layers {
+  keys: Vec<LayerKey>,
+}
+
+impl Layers {
+  fn index(&mut self, key: LayerKey) -> usize {
+    match self.keys.iter().position(|k| *k == key) {
+      Some(index) => index,
+      None => {
+        self.keys.push(key);
+        self.keys.len() - 1
+      }
+    }
+  }
+}
+
+/// Whether the part can be stored as a hard link to the first one, with the
+/// same host inode. The first one needs to be in every layer the part is.
+fn links_to(part: &Part, first: &Part) -> bool {
+  (first.arch.is_none() || first.arch == part.arch)
+    && first.to != part.to
+    && first.mode == part.mode
+    && first.owner == part.owner
+}
+
+fn payload_fingerprint(
+  base: &str,
+  settings: &oci::Settings,
+  payload: &Payload,
+  digests: &[String],
+) -> String {
+  let files = payload
+    .parts
+    .iter()
+    .zip(digests)
+    .map(|(part, digest)| FileFingerprint {
+      layer: part.layer,
+      arch: part.arch.clone(),
+      to: part.to.clone(),
+      mode: part.mode,
+      owner: part.owner.clone(),
+      digest: digest.clone(),
+    })
+    .collect();
+  fingerprint(base, settings, files)
+}
+
+struct FileFingerprint {
+  layer: usize,
+  arch: Option<Arch>,
+  to: String,
+  mode: u32,
+  owner: Option<Owner>,
+  digest: String,
+}
+
+/// Digests the canonical, order independent, form of the build inputs.
+fn fingerprint(
+  base: &str,
+  settings: &oci::Settings,
+  files: Vec<FileFingerprint>,
+) -> String {
+  let mut archs: Vec<String> =
+    settings.archs.iter().map(|a| a.to_string()).collect();
+  archs.sort();
+  let mut lines: Vec<String> = files
+    .iter()
+    .map(|f| {
+      let arch = f.arch.as_ref().map(|a| a.to_string());
+      let owner = f.owner.as_ref().map(|o| o.to_string());
+      format!(
+        "file {} {} {} {:o} {} {}",
+        f.layer,
+        arch.unwrap_or_else(|| "*".to_string()),
+        f.to,
+        f.mode,
+        owner.unwrap_or_else(|| "-".to_string()),
+        f.digest
+      )
+    })
+    .collect();
+  lines.sort();
+  let mut hasher = Sha256::new();
+  hasher.update(format!("ocilot {}\n", env!("CARGO_PKG_VERSION")));
+  hasher.update(format!("base {}\n", base));
+  hasher.update(format!("arch {}\n", archs.join(",")));
+  if let Some(created) = settings.created {
+    let secs = created
+      .duration_since(SystemTime::UNIX_EPOCH)
+      .map(|d| d.as_secs())
+      .unwrap_or_default();
+    hasher.update(format!("created {}\n", secs));
+  }
+  if settings.config != oci::ConfigChanges::default() {
+    hasher.update(format!("config {:?}\n", settings.config));
+  }
+  for line in lines {
+    hasher.update(line);
+    hasher.update("\n");
+  }
+  format!("{:x}", hasher.finalize())
+}
+
+/// Computes an absolute path within the image for a resolved artifact file.
+///
+/// The `to` is treated as a directory when it ends with a slash, when the
+/// artifact is a directory, or when it resolved to many files. The file is
+/// placed within such directory by its path relative to the artifact root,
+/// like `COPY` does. Without `to`, the file keeps its path relative to the
+/// current directory.
+fn destination(
+  to: &Option<String>,
+  file: &fs::ResolvedFile,
+  many: bool,
+) -> Result<String> {
+  match to {
+    Some(to) => {
+      let to = image_path(Path::new(to));
+      if many || file.from_dir || to.ends_with('/') {
+        let relative = image_path(&file.relative);
+        if relative == "/" {
+          return Err(Error::invalid_input(
+            format!("not a file: {:?}", file.path).as_str(),
+          ));
+        }
+        Ok(format!("{}{}", to.trim_end_matches('/'), relative))
+      } else {
+        Ok(to)
+      }
+    }
+    None => {
+      let cwd = std::env::current_dir()?;
+      let from = &file.path;
+      Ok(image_path(from.strip_prefix(&cwd).unwrap_or(from)))
+    }
+  }
+}
+
+/// Normalizes a path into an absolute, slash separated path within the image.
+fn image_path(p: &Path) -> String {
+  let mut parts = Vec::new();
+  for component in p.components() {
+    match component {
+      Component::Normal(part) => parts.push(part.to_string_lossy().to_string()),
+      Component::ParentDir => {
+        parts.pop();
+      }
+      _ => {}
+    }
+  }
+  let trailing = p.to_string_lossy().ends_with('/') && !parts.is_empty();
+  format!("/{}{}", parts.join("/"), if trailing { "/" } else { "" })
+}
+
 impl Hash for Build {
   fn hash<H: Hasher>(&self, state: &mut H) {
     self.base.hash(state);
@@ -177,6 +1135,13 @@ impl Hash for Build {
     for arch in &self.arch {
       arch.hash(state)
     }
+    self.timestamp.hash(state);
+    self.config.hash(state);
+    for link in &self.symlinks {
+      link.hash(state)
+    }
+    self.layering.hash(state);
+    self.sbom.hash(state);
   }
 }
 
@@ -188,3 +1153,1200 @@ impl Hash for ImageName {
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use std::collections::HashSet;
+  use std::io;
+  use std::path::{Path, PathBuf};
+  use std::sync::{Arc, Mutex};
+  use std::time::{Duration, SystemTime};
+
+  use crate::build::FileFingerprint;
+  use crate::error::{Error, Result};
+  use crate::observe::{BuildObserver, Event};
+  use crate::oci::Link;
+  use crate::{build, fs, oci, Arch, Artifact, Id, Owner, Symlink};
+
+  #[test]
+  fn destination() {
+    let cases = [
+      (Some("/usr/bin/acme"), "target/acme", false, "/usr/bin/acme"),
+      (
+        Some("/usr/lib/app/"),
+        "target/app.jar",
+        false,
+        "/usr/lib/app/app.jar",
+      ),
+      (
+        Some("/usr/lib/app"),
+        "target/app.jar",
+        true,
+        "/usr/lib/app/app.jar",
+      ),
+      (Some("usr/lib/../share"), "a.txt", false, "/usr/share"),
+      (None, "relative/file.txt", false, "/relative/file.txt"),
+      (None, "./file.txt", false, "/file.txt"),
+      (None, "/absolute/file.txt", false, "/absolute/file.txt"),
+    ];
+
+    for (to, from, many, want) in cases {
+      let to = to.map(|t| t.to_string());
+      let file = fs::ResolvedFile {
+        path: PathBuf::from(from),
+        relative: PathBuf::from(Path::new(from).file_name().unwrap()),
+        from_dir: false,
+      };
+      let got = build::destination(&to, &file, many).unwrap();
+      assert_eq!(got, want);
+    }
+
+    let nested = |relative: &str, from_dir: bool| fs::ResolvedFile {
+      path: Path::new("static").join(relative),
+      relative: PathBuf::from(relative),
+      from_dir,
+    };
+    let cases = [
+      (
+        "/srv/www",
+        nested("css/site.css", false),
+        true,
+        "/srv/www/css/site.css",
+      ),
+      (
+        "/srv/www/",
+        nested("index.html", false),
+        false,
+        "/srv/www/index.html",
+      ),
+      (
+        "/srv/www",
+        nested("img/logo.png", true),
+        false,
+        "/srv/www/img/logo.png",
+      ),
+      (
+        "/srv/index.html",
+        nested("main.html", false),
+        false,
+        "/srv/index.html",
+      ),
+    ];
+
+    for (to, file, many, want) in cases {
+      let got = build::destination(&Some(to.to_string()), &file, many).unwrap();
+      assert_eq!(got, want);
+    }
+  }
+
+  #[test]
+  fn fingerprint() {
+    let file = |arch: Option<Arch>, to: &str, digest: &str| FileFingerprint {
+      layer: 0,
+      arch,
+      to: to.to_string(),
+      mode: 0o644,
+      owner: None,
+      digest: digest.to_string(),
+    };
+    let settings = oci::Settings {
+      archs: HashSet::from([Arch::Amd64, Arch::Arm64]),
+      created: None,
+      config: oci::ConfigChanges::default(),
+      clock: oci::Clock::default(),
+    };
+    let one = build::fingerprint(
+      "abc",
+      &settings,
+      vec![
+        file(None, "/etc/app.conf", "sha256:01"),
+        file(Some(Arch::Amd64), "/usr/bin/app", "sha256:02"),
+      ],
+    );
+    let reordered = build::fingerprint(
+      "abc",
+      &settings,
+      vec![
+        file(Some(Arch::Amd64), "/usr/bin/app", "sha256:02"),
+        file(None, "/etc/app.conf", "sha256:01"),
+      ],
+    );
+    let other_base = build::fingerprint(
+      "def",
+      &settings,
+      vec![file(None, "/etc/app.conf", "sha256:01")],
+    );
+    let moved = build::fingerprint(
+      "abc",
+      &settings,
+      vec![
+        file(None, "/etc/app/app.conf", "sha256:01"),
+        file(Some(Arch::Amd64), "/usr/bin/app", "sha256:02"),
+      ],
+    );
+
+    let stamped = build::fingerprint(
+      "abc",
+      &oci::Settings {
+        created: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1)),
+        ..settings.clone()
+      },
+      vec![
+        file(None, "/etc/app.conf", "sha256:01"),
+        file(Some(Arch::Amd64), "/usr/bin/app", "sha256:02"),
+      ],
+    );
+
+    let chmoded = build::fingerprint(
+      "abc",
+      &settings,
+      vec![
+        FileFingerprint {
+          mode: 0o600,
+          ..file(None, "/etc/app.conf", "sha256:01")
+        },
+        file(Some(Arch::Amd64), "/usr/bin/app", "sha256:02"),
+      ],
+    );
+    let chowned = build::fingerprint(
+      "abc",
+      &settings,
+      vec![
+        FileFingerprint {
+          owner: Some(Owner {
+            user: Id::Numeric(65532),
+            group: None,
+          }),
+          ..file(None, "/etc/app.conf", "sha256:01")
+        },
+        file(Some(Arch::Amd64), "/usr/bin/app", "sha256:02"),
+      ],
+    );
+
+    let relayered = build::fingerprint(
+      "abc",
+      &settings,
+      vec![
+        file(None, "/etc/app.conf", "sha256:01"),
+        FileFingerprint {
+          layer: 1,
+          ..file(Some(Arch::Amd64), "/usr/bin/app", "sha256:02")
+        },
+      ],
+    );
+
+    let configured = build::fingerprint(
+      "abc",
+      &oci::Settings {
+        config: oci::ConfigChanges {
+          entrypoint: oci::Change::Set(vec!["/usr/bin/app".to_string()]),
+          ..Default::default()
+        },
+        ..settings.clone()
+      },
+      vec![
+        file(None, "/etc/app.conf", "sha256:01"),
+        file(Some(Arch::Amd64), "/usr/bin/app", "sha256:02"),
+      ],
+    );
+
+    assert_eq!(one, reordered);
+    assert_ne!(one, configured);
+    assert_ne!(one, stamped);
+    assert_ne!(one, chmoded);
+    assert_ne!(one, chowned);
+    assert_ne!(one, relayered);
+    assert_ne!(one, other_base);
+    assert_ne!(one, moved);
+    assert_eq!(one.len(), 64);
+  }
+
+  /// A command over the stub files, reaching the registry and the cache given.
+  fn command(
+    registry: Box<dyn oci::Registry>,
+    cache: Box<dyn oci::Cache>,
+  ) -> build::Command {
+    build::Command {
+      fs: build::FileSystem {
+        resolver: Box::new(Resolver {}),
+        files: Box::new(Files {}),
+      },
+      oci: build::Oci {
+        registry,
+        cache,
+        signer: None,
+        verifier: None,
+        policy: Default::default(),
+      },
+      observer: None,
+    }
+  }
+
+  /// A build of the artifacts into an app image, on top of a distroless base,
+  /// for the base architectures.
+  fn build(artifacts: Vec<Artifact>) -> build::Build {
+    build::Build {
+      base: "gcr.io/distroless/static-debian11".to_string(),
+      artifacts,
+      image: build::ImageName {
+        image: "quay.io/acme/app".to_string(),
+        tags: HashSet::new(),
+      },
+      arch: HashSet::new(),
+      timestamp: None,
+      config: Default::default(),
+      symlinks: vec![],
+      layering: Default::default(),
+      sbom: None,
+    }
+  }
+
+  /// An artifact copying the host path to the image one, with no options.
+  fn artifact(from: &str, to: &str) -> Artifact {
+    Artifact {
+      arch: None,
+      from: from.to_string(),
+      to: Some(to.to_string()),
+      mode: None,
+      owner: None,
+      keep_symlinks: false,
+      exclude: vec![],
+      gitignore: false,
+      layer: None,
+    }
+  }
+
+  fn named(image: &str) -> build::ImageName {
+    build::ImageName {
+      image: image.to_string(),
+      tags: HashSet::new(),
+    }
+  }
+
+  #[test]
+  fn plan() {
+    let cmd = command(Box::new(Offline {}), Box::new(Offline {}));
+    let mut b = build::Build {
+      arch: HashSet::from([Arch::Arm64, Arch::Amd64]),
+      ..build(vec![
+        artifact("static/*.css", "/srv/www/"),
+        Artifact {
+          arch: Some(Arch::Arm64),
+          ..artifact("target/acme-arm64", "/usr/bin/acme")
+        },
+        Artifact {
+          arch: Some(Arch::S390x),
+          ..artifact("target/acme-s390x", "/usr/bin/acme")
+        },
+      ])
+    };
+
+    let plan = cmd.plan(&b).unwrap();
+
+    assert_eq!(plan.archs, vec![Arch::Amd64, Arch::Arm64]);
+    let files: Vec<(Option<Arch>, &str, u64)> = plan
+      .files
+      .iter()
+      .map(|f| (f.arch.clone(), f.to.as_str(), f.size))
+      .collect();
+    assert_eq!(
+      files,
+      vec![
+        (Some(Arch::Amd64), "/srv/www/a.css", 12),
+        (Some(Arch::Arm64), "/srv/www/a.css", 12),
+        (Some(Arch::Amd64), "/srv/www/b.css", 12),
+        (Some(Arch::Arm64), "/srv/www/b.css", 12),
+        (Some(Arch::Arm64), "/usr/bin/acme", 17),
+      ]
+    );
+
+    b.arch.clear();
+    let plan = cmd.plan(&b).unwrap();
+
+    assert!(plan.archs.is_empty());
+    let archs: Vec<Option<Arch>> =
+      plan.files.iter().map(|f| f.arch.clone()).collect();
+    assert_eq!(
+      archs,
+      vec![None, None, Some(Arch::Arm64), Some(Arch::S390x)]
+    );
+  }
+
+  #[test]
+  fn links() {
+    let cmd = command(Box::new(Offline {}), Box::new(Offline {}));
+    let b = build::Build {
+      symlinks: vec![Symlink {
+        arch: None,
+        path: "/usr/bin/acme".to_string(),
+        target: "/opt/acme/bin/acme".to_string(),
+      }],
+      ..build(vec![
+        artifact("target/acme", "/usr/local/bin/acme"),
+        artifact("target/acme", "/opt/acme/bin/acme"),
+        Artifact {
+          keep_symlinks: true,
+          ..artifact("links/current", "/opt/current")
+        },
+        artifact("links/current", "/opt/followed"),
+      ])
+    };
+
+    let payload = cmd.construct_payload(&b).unwrap();
+    let digests = cmd.digests(&payload).unwrap();
+    let materials = cmd.materials(&payload, &digests, true).unwrap();
+    let mut got: Vec<&str> = materials.iter().map(|m| m.to.as_str()).collect();
+    got.sort_unstable();
+    assert_eq!(
+      got,
+      vec!["/opt/acme/bin/acme", "/opt/followed", "/usr/local/bin/acme"]
+    );
+    let empty =
+      "sha256:e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+    assert!(materials.iter().all(|m| m.digest == empty));
+    let empty = "da39a3ee5e6b4b0d3255bfef95601890afd80709";
+    assert!(materials.iter().all(|m| m.sha1.as_deref() == Some(empty)));
+    let packages: Vec<(&str, u64, usize)> = materials
+      .iter()
+      .map(|m| (m.to.as_str(), m.size, m.packages.len()))
+      .collect();
+    assert_eq!(
+      packages,
+      vec![
+        ("/usr/local/bin/acme", 11, 1),
+        ("/opt/acme/bin/acme", 11, 1),
+        ("/opt/followed", 13, 0),
+      ]
+    );
+
+    let mut layers = cmd.open_payload(payload).unwrap();
+    assert_eq!(layers.len(), 1);
+    let inputs = layers.remove(0);
+
+    let got: Vec<(&str, u32, Option<oci::Link>)> = inputs
+      .iter()
+      .map(|i| (i.to.as_str(), i.mode, i.link.clone()))
+      .collect();
+    assert_eq!(
+      got,
+      vec![
+        ("/opt/acme/bin/acme", 0o644, None),
+        (
+          "/opt/current",
+          0o777,
+          Some(Link::Symbolic("../real".to_string()))
+        ),
+        ("/opt/followed", 0o644, None),
+        (
+          "/usr/bin/acme",
+          0o777,
+          Some(Link::Symbolic("/opt/acme/bin/acme".to_string()))
+        ),
+        (
+          "/usr/local/bin/acme",
+          0o644,
+          Some(Link::Hard("/opt/acme/bin/acme".to_string()))
+        ),
+      ]
+    );
+  }
+
+  #[test]
+  fn unreadable_packages() {
+    let cmd = command(Box::new(Offline {}), Box::new(Offline {}));
+    let b = build(vec![
+      artifact("target/acme", "/usr/bin/acme"),
+      artifact("truncated/acme", "/usr/bin/truncated"),
+    ]);
+
+    let payload = cmd.construct_payload(&b).unwrap();
+    let digests = cmd.digests(&payload).unwrap();
+    let materials = cmd.materials(&payload, &digests, true).unwrap();
+
+    let packages: Vec<(&str, usize)> = materials
+      .iter()
+      .map(|m| (m.to.as_str(), m.packages.len()))
+      .collect();
+    assert_eq!(
+      packages,
+      vec![("/usr/bin/acme", 1), ("/usr/bin/truncated", 0)]
+    );
+  }
+
+  #[test]
+  fn layering() {
+    let artifact = |from: &str, layer: Option<&str>| Artifact {
+      layer: layer.map(|l| l.to_string()),
+      ..artifact(from, "/opt/acme/")
+    };
+    let cmd = command(Box::new(Offline {}), Box::new(Offline {}));
+    let mut b = build::Build {
+      symlinks: vec![Symlink {
+        arch: None,
+        path: "/opt/acme/current.jar".to_string(),
+        target: "app.jar".to_string(),
+      }],
+      layering: build::Layering::Single,
+      ..build(vec![
+        artifact("deps/a.jar", Some("deps")),
+        artifact("app.jar", None),
+        artifact("deps/b.jar", Some("deps")),
+        artifact("app.conf", None),
+      ])
+    };
+    let layers = |b: &build::Build| -> Vec<(usize, String)> {
+      let payload = cmd.construct_payload(b).unwrap();
+      payload.parts.into_iter().map(|p| (p.layer, p.to)).collect()
+    };
+
+    assert_eq!(
+      layers(&b),
+      vec![
+        (0, "/opt/acme/a.jar".to_string()),
+        (1, "/opt/acme/app.jar".to_string()),
+        (0, "/opt/acme/b.jar".to_string()),
+        (1, "/opt/acme/app.conf".to_string()),
+        (1, "/opt/acme/current.jar".to_string()),
+      ]
+    );
+
+    b.layering = build::Layering::PerArtifact;
+
+    assert_eq!(
+      layers(&b),
+      vec![
+        (0, "/opt/acme/a.jar".to_string()),
+        (1, "/opt/acme/app.jar".to_string()),
+        (0, "/opt/acme/b.jar".to_string()),
+        (2, "/opt/acme/app.conf".to_string()),
+        (3, "/opt/acme/current.jar".to_string()),
+      ]
+    );
+    let payload = cmd.construct_payload(&b).unwrap();
+    let sizes: Vec<usize> = cmd
+      .open_payload(payload)
+      .unwrap()
+      .iter()
+      .map(|layer| layer.len())
+      .collect();
+    assert_eq!(sizes, vec![2, 1, 1, 1]);
+  }
+
+  #[test]
+  fn execute_all() {
+    let build = |base: &str, image: &str, arch: &[Arch]| build::Build {
+      base: base.to_string(),
+      image: named(image),
+      arch: arch.iter().cloned().collect(),
+      ..build(vec![artifact("target/acme", "/usr/bin/acme")])
+    };
+    let command = |images: &[(build::ImageName, String)]| {
+      command(
+        Box::new(Known {}),
+        Box::new(History {
+          images: images.to_vec(),
+        }),
+      )
+    };
+    let distroless = "gcr.io/distroless/static-debian11";
+    let amd64 = [Arch::Amd64];
+    let builds = vec![
+      build("local:quay.io/acme/app", "quay.io/acme/web", &amd64),
+      build(distroless, "quay.io/acme/app", &amd64),
+      build(distroless, "quay.io/acme/broken", &[]),
+      build("local:quay.io/acme/broken", "quay.io/acme/docs", &amd64),
+      build("local:quay.io/acme/loop", "quay.io/acme/loop", &amd64),
+    ];
+    // the app is built before, and the web on top of it
+    let mut images = vec![];
+    for b in [&builds[1], &builds[0]] {
+      let fingerprint = command(&images).fingerprint(b).unwrap();
+      images.push((b.image.clone(), fingerprint));
+    }
+    let cmd = command(&images);
+
+    let got = cmd.execute_all(&builds, 2);
+
+    assert_eq!(got.len(), 5);
+    assert!(matches!(got[0], Ok(build::Built::Cached(_))));
+    assert!(matches!(got[1], Ok(build::Built::Cached(_))));
+    for res in &got[2..] {
+      assert!(matches!(res, Err(Error::InvalidInput { .. })), "{:?}", res);
+    }
+  }
+
+  #[test]
+  fn local_pinned() {
+    let app = build::ImageName {
+      image: "quay.io/acme/app".to_string(),
+      tags: HashSet::from(["v1".to_string()]),
+    };
+    let cmd = command(
+      Box::new(Offline {}),
+      Box::new(History {
+        images: vec![(app, "fp".to_string())],
+      }),
+    );
+    let build = |base: &str| build::Build {
+      base: base.to_string(),
+      image: named("quay.io/acme/web"),
+      ..build(vec![])
+    };
+
+    let tagged = cmd.fingerprint(&build("local:quay.io/acme/app:v1"));
+    let pinned = cmd.fingerprint(&build("local:quay.io/acme/app@sha256:16"));
+    let other = cmd.fingerprint(&build("local:quay.io/acme/app@sha256:17"));
+    let renamed = cmd.fingerprint(&build("local:quay.io/acme/web@sha256:16"));
+
+    assert_eq!(pinned.unwrap(), tagged.unwrap());
+    for res in [other, renamed] {
+      assert!(matches!(res, Err(Error::InvalidInput { .. })), "{:?}", res);
+    }
+  }
+
+  #[test]
+  fn lookup_built() {
+    let tagged = |tags: &[&str]| build::ImageName {
+      image: "quay.io/acme/app".to_string(),
+      tags: tags.iter().map(|t| t.to_string()).collect(),
+    };
+    let cmd = command(
+      Box::new(Offline {}),
+      Box::new(History {
+        images: vec![(tagged(&["v1", "v2"]), "fp".to_string())],
+      }),
+    );
+
+    let hits = [
+      cmd.lookup_built("fp", &tagged(&["v1"])),
+      cmd.lookup_built("fp", &tagged(&["v2", "v1"])),
+      cmd.lookup_built("fp", &tagged(&[])),
+    ];
+    let misses = [
+      cmd.lookup_built("fp", &tagged(&["v1", "v3"])),
+      cmd.lookup_built("other", &tagged(&["v1"])),
+      cmd.lookup_built("fp", &named("quay.io/acme/web")),
+    ];
+
+    for res in hits {
+      assert!(matches!(res, Ok(Some(_))), "{:?}", res);
+    }
+    for res in misses {
+      assert!(matches!(res, Ok(None)), "{:?}", res);
+    }
+  }
+
+  #[test]
+  fn verify_reproducible() {
+    let b = build::Build {
+      base: "quay.io/acme/base".to_string(),
+      image: named("quay.io/acme/web"),
+      arch: HashSet::from([Arch::Amd64]),
+      timestamp: Some(SystemTime::UNIX_EPOCH),
+      ..build(vec![artifact("static/*.css", "/srv/www/")])
+    };
+    let command =
+      |leak: Leak| command(Box::new(Stacking { leak }), Box::new(Offline {}));
+    let untimed = build::Build {
+      timestamp: None,
+      ..b.clone()
+    };
+    let res = command(Leak::Nothing).verify_reproducible(&untimed);
+    assert!(matches!(res, Err(Error::InvalidInput { .. })), "{:?}", res);
+
+    let sound = command(Leak::Nothing).verify_reproducible(&b);
+    let ordered = command(Leak::Order).verify_reproducible(&b);
+    let clocked = command(Leak::Clock).verify_reproducible(&b);
+
+    assert_eq!(sound.unwrap().digest, "/srv/www/a.css,/srv/www/b.css");
+    for res in [ordered, clocked] {
+      assert!(
+        matches!(res, Err(Error::NotReproducible { .. })),
+        "{:?}",
+        res
+      );
+    }
+  }
+
+  #[test]
+  fn observed() {
+    let b = build::Build {
+      image: named("quay.io/acme/web"),
+      arch: HashSet::from([Arch::Amd64]),
+      ..build(vec![artifact("static/*.css", "/srv/www/")])
+    };
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let command = |images: Vec<(build::ImageName, String)>| build::Command {
+      observer: Some(Box::new(Recorder {
+        events: events.clone(),
+      })),
+      ..command(Box::new(Known {}), Box::new(History { images }))
+    };
+    let fingerprint = command(vec![]).fingerprint(&b).unwrap();
+    events.lock().unwrap().clear();
+    let cmd = command(vec![(b.image.clone(), fingerprint.clone())]);
+
+    cmd.execute(&b).unwrap();
+
+    let got = events.lock().unwrap().clone();
+    assert_eq!(
+      got,
+      vec![
+        (
+          b.image.image.clone(),
+          Event::ArtifactResolved {
+            from: "static/*.css".to_string(),
+            files: 2,
+          }
+        ),
+        (
+          b.image.image.clone(),
+          Event::CacheHit {
+            digest: "sha256:16".to_string(),
+            fingerprint,
+          }
+        ),
+      ]
+    );
+  }
+
+  /// Records the events, by the images built.
+  #[derive(Debug)]
+  struct Recorder {
+    events: Arc<Mutex<Vec<(String, Event)>>>,
+  }
+
+  impl BuildObserver for Recorder {
+    fn observe(&self, image: &build::ImageName, event: &Event) {
+      let mut events = self.events.lock().unwrap();
+      events.push((image.image.clone(), event.clone()));
+    }
+  }
+
+  #[derive(Debug)]
+  struct Resolver {}
+
+  impl fs::ArtifactResolver for Resolver {
+    fn resolve(&self, ar: &Artifact) -> Result<Vec<fs::ResolvedFile>> {
+      let paths = match ar.from.as_str() {
+        "static/*.css" => vec!["static/b.css", "static/a.css"],
+        from => vec![from],
+      };
+      Ok(
+        paths
+          .into_iter()
+          .map(|path| fs::ResolvedFile {
+            path: PathBuf::from(path),
+            relative: PathBuf::from(Path::new(path).file_name().unwrap()),
+            from_dir: false,
+          })
+          .collect(),
+      )
+    }
+  }
+
+  #[derive(Debug)]
+  struct Files {}
+
+  impl fs::Files for Files {
+    fn read(&self, _: &Path) -> io::Result<Box<dyn io::Read>> {
+      Ok(Box::new(io::empty()))
+    }
+
+    fn mode(&self, _: &Path) -> io::Result<u32> {
+      Ok(0o644)
+    }
+
+    fn size(&self, p: &Path) -> io::Result<u64> {
+      Ok(p.to_string_lossy().len() as u64)
+    }
+
+    fn symlink(&self, p: &Path) -> io::Result<Option<PathBuf>> {
+      Ok(Some(PathBuf::from("../real")).filter(|_| p.starts_with("links")))
+    }
+
+    fn inode(&self, p: &Path) -> io::Result<Option<(u64, u64)>> {
+      Ok(Some((1, 1)).filter(|_| p.starts_with("target")))
+    }
+
+    fn packages(&self, p: &Path) -> io::Result<Vec<fs::Package>> {
+      if p.starts_with("truncated") {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+      }
+      let acme = fs::Package {
+        name: "acme".to_string(),
+        version: "1.0.0".to_string(),
+        source: "local".to_string(),
+      };
+      Ok(
+        Some(acme)
+          .into_iter()
+          .filter(|_| p.starts_with("target"))
+          .collect(),
+      )
+    }
+  }
+
+  #[test]
+  fn verified_bases() {
+    let oci = build::Oci {
+      registry: Box::new(Signed {}),
+      cache: Box::new(Offline {}),
+      signer: None,
+      verifier: Some(Box::new(Trusting {})),
+      policy: Default::default(),
+    };
+    let bases = build::Bases::default();
+
+    let got = bases.digest(&oci, "quay.io/acme/base:v1").unwrap();
+    assert_eq!(got, "trusted");
+    let got = bases.digest(&oci, "quay.io/acme/forged:v1");
+    assert!(
+      matches!(&got, Err(Error::UnverifiedBase { digest, .. }) if digest == "forged"),
+      "{:?}",
+      got
+    );
+    assert_eq!(
+      build::pinned("quay.io/acme/base:v1", "trusted"),
+      "quay.io/acme/base:v1@sha256:trusted"
+    );
+    assert_eq!(
+      build::pinned("quay.io/acme/base@sha256:other", "trusted"),
+      "quay.io/acme/base@sha256:trusted"
+    );
+  }
+
+  #[test]
+  fn tampered_base() {
+    let fetch = |registry: Box<dyn oci::Registry>| {
+      let cmd = command(registry, Box::new(Offline {}));
+      let bases = build::Bases::default();
+      let notify = |_| {};
+      bases
+        .fetch(&cmd.oci, "quay.io/acme/base:v1", &HashSet::new(), &notify)
+        .map(|base| base.digest())
+    };
+
+    let sound = fetch(Box::new(Stacking {
+      leak: Leak::Nothing,
+    }));
+    let tampered = fetch(Box::new(Tampering {}));
+
+    assert_eq!(sound.unwrap(), "sha256:base");
+    assert!(
+      matches!(&tampered, Err(Error::InvalidInput { message, .. }) if message.contains("sha256:tampered")),
+      "{:?}",
+      tampered
+    );
+  }
+
+  /// Fails the test on any attempt to reach a registry, or the cache.
+  #[derive(Debug)]
+  struct Offline {}
+
+  impl oci::Registry for Offline {
+    fn fetch(
+      &self,
+      image: &str,
+      _: &HashSet<Arch>,
+      _: &(dyn Fn(usize, usize) + Sync),
+    ) -> Result<Box<dyn oci::Image>> {
+      panic!("unexpected fetch of {}", image)
+    }
+
+    fn platforms(&self, image: &str) -> Result<HashSet<Arch>> {
+      panic!("unexpected platforms lookup of {}", image)
+    }
+
+    fn digest(&self, image: &str) -> Result<String> {
+      panic!("unexpected digest lookup of {}", image)
+    }
+
+    fn push_signature(
+      &self,
+      image: &str,
+      _: &str,
+      _: &oci::Signature,
+      _: oci::SignatureScheme,
+    ) -> Result<()> {
+      panic!("unexpected signature push of {}", image)
+    }
+
+    fn signatures(&self, image: &str, _: &str) -> Result<Vec<oci::Signature>> {
+      panic!("unexpected signatures lookup of {}", image)
+    }
+  }
+
+  impl oci::Cache for Offline {
+    fn list(&self) -> Result<Vec<Box<dyn oci::Image>>> {
+      panic!("unexpected cache listing")
+    }
+
+    fn find(
+      &self,
+      image: &str,
+      _: Option<&str>,
+    ) -> Result<Vec<Box<dyn oci::Image>>> {
+      panic!("unexpected cache lookup of {}", image)
+    }
+
+    fn get(&self, digest: &str) -> Result<Option<Box<dyn oci::Image>>> {
+      panic!("unexpected cache lookup of {}", digest)
+    }
+  }
+
+  /// What the constructions on top of a stacked base let into the digests of
+  /// the images they build, besides the files.
+  #[derive(PartialEq, Eq, Debug, Clone, Copy)]
+  enum Leak {
+    Nothing,
+    Order,
+    Clock,
+  }
+
+  /// Serves a stacked base image, with the given leak.
+  #[derive(Debug)]
+  struct Stacking {
+    leak: Leak,
+  }
+
+  impl oci::Registry for Stacking {
+    fn fetch(
+      &self,
+      _: &str,
+      _: &HashSet<Arch>,
+      _: &(dyn Fn(usize, usize) + Sync),
+    ) -> Result<Box<dyn oci::Image>> {
+      Ok(Box::new(Stacked {
+        leak: self.leak,
+        digest: "sha256:base".to_string(),
+      }))
+    }
+
+    fn platforms(&self, _: &str) -> Result<HashSet<Arch>> {
+      Ok(HashSet::from([Arch::Amd64]))
+    }
+
+    fn digest(&self, _: &str) -> Result<String> {
+      Ok("sha256:base".to_string())
+    }
+
+    fn push_signature(
+      &self,
+      image: &str,
+      _: &str,
+      _: &oci::Signature,
+      _: oci::SignatureScheme,
+    ) -> Result<()> {
+      panic!("unexpected signature push of {}", image)
+    }
+
+    fn signatures(&self, image: &str, _: &str) -> Result<Vec<oci::Signature>> {
+      panic!("unexpected signatures lookup of {}", image)
+    }
+  }
+
+  /// Serves other content than the digest it resolves the base image to.
+  #[derive(Debug)]
+  struct Tampering {}
+
+  impl oci::Registry for Tampering {
+    fn fetch(
+      &self,
+      _: &str,
+      _: &HashSet<Arch>,
+      _: &(dyn Fn(usize, usize) + Sync),
+    ) -> Result<Box<dyn oci::Image>> {
+      Ok(Box::new(Stacked {
+        leak: Leak::Nothing,
+        digest: "sha256:tampered".to_string(),
+      }))
+    }
+
+    fn platforms(&self, _: &str) -> Result<HashSet<Arch>> {
+      Ok(HashSet::from([Arch::Amd64]))
+    }
+
+    fn digest(&self, _: &str) -> Result<String> {
+      Ok("sha256:base".to_string())
+    }
+
+    fn push_signature(
+      &self,
+      image: &str,
+      _: &str,
+      _: &oci::Signature,
+      _: oci::SignatureScheme,
+    ) -> Result<()> {
+      panic!("unexpected signature push of {}", image)
+    }
+
+    fn signatures(&self, image: &str, _: &str) -> Result<Vec<oci::Signature>> {
+      panic!("unexpected signatures lookup of {}", image)
+    }
+  }
+
+  /// An image digested by the destinations of the files stacked on it.
+  #[derive(Debug)]
+  struct Stacked {
+    leak: Leak,
+    digest: String,
+  }
+
+  impl oci::Image for Stacked {
+    fn digest(&self) -> String {
+      self.digest.clone()
+    }
+
+    fn name(&self) -> build::ImageName {
+      build::ImageName {
+        image: "quay.io/acme/base".to_string(),
+        tags: HashSet::new(),
+      }
+    }
+
+    fn created(&self) -> SystemTime {
+      SystemTime::UNIX_EPOCH
+    }
+
+    fn fingerprint(&self) -> Option<String> {
+      None
+    }
+
+    fn platforms(&self) -> Result<HashSet<Arch>> {
+      Ok(HashSet::from([Arch::Amd64]))
+    }
+
+    fn attest(&self, _: &build::Provenance) -> Result<()> {
+      Ok(())
+    }
+
+    fn sign(&self, _: &oci::Signature) -> Result<()> {
+      Ok(())
+    }
+
+    fn construct_new(
+      &self,
+      settings: &oci::Settings,
+    ) -> Box<dyn oci::Construction> {
+      Box::new(Stack {
+        leak: self.leak,
+        clock: settings.clock,
+        files: vec![],
+      })
+    }
+  }
+
+  /// Stacks the files, sorted, unless it leaks their order.
+  struct Stack {
+    leak: Leak,
+    clock: oci::Clock,
+    files: Vec<String>,
+  }
+
+  impl oci::Construction for Stack {
+    fn add(&mut self, files: Vec<oci::Input>) -> Result<Vec<oci::LayerInfo>> {
+      let mut added: Vec<String> = files.into_iter().map(|f| f.to).collect();
+      if self.leak != Leak::Order {
+        added.sort();
+      }
+      self.files.extend(added);
+      Ok(vec![])
+    }
+
+    fn build(
+      &self,
+      _: &build::ImageName,
+      _: &str,
+    ) -> Result<Box<dyn oci::Image>> {
+      let mut digest = self.files.join(",");
+      if self.leak == Leak::Clock {
+        let now = self.clock.now().duration_since(SystemTime::UNIX_EPOCH);
+        digest.push_str(&format!("@{}", now.unwrap().as_secs()));
+      }
+      Ok(Box::new(Stacked {
+        leak: self.leak,
+        digest,
+      }))
+    }
+  }
+
+  /// Knows the digest of any base image, but the platforms of none.
+  #[derive(Debug)]
+  struct Known {}
+
+  impl oci::Registry for Known {
+    fn fetch(
+      &self,
+      image: &str,
+      _: &HashSet<Arch>,
+      _: &(dyn Fn(usize, usize) + Sync),
+    ) -> Result<Box<dyn oci::Image>> {
+      panic!("unexpected fetch of {}", image)
+    }
+
+    fn platforms(&self, image: &str) -> Result<HashSet<Arch>> {
+      Err(Error::invalid_input(&format!("unknown image: {}", image)))
+    }
+
+    fn digest(&self, _: &str) -> Result<String> {
+      Ok("sha256:base".to_string())
+    }
+
+    fn push_signature(
+      &self,
+      image: &str,
+      _: &str,
+      _: &oci::Signature,
+      _: oci::SignatureScheme,
+    ) -> Result<()> {
+      panic!("unexpected signature push of {}", image)
+    }
+
+    fn signatures(&self, image: &str, _: &str) -> Result<Vec<oci::Signature>> {
+      panic!("unexpected signatures lookup of {}", image)
+    }
+  }
+
+  /// A cache of images built before, by their names and fingerprints.
+  #[derive(Debug)]
+  struct History {
+    images: Vec<(build::ImageName, String)>,
+  }
+
+  impl oci::Cache for History {
+    fn list(&self) -> Result<Vec<Box<dyn oci::Image>>> {
+      Ok(
+        self
+          .images
+          .iter()
+          .map(|(name, fingerprint)| {
+            Box::new(CachedImage {
+              name: name.clone(),
+              fingerprint: fingerprint.clone(),
+            }) as Box<dyn oci::Image>
+          })
+          .collect(),
+      )
+    }
+
+    fn find(
+      &self,
+      image: &str,
+      tag: Option<&str>,
+    ) -> Result<Vec<Box<dyn oci::Image>>> {
+      let mut found = self.list()?;
+      found.retain(|i| {
+        let name = i.name();
+        name.image == image && tag.is_none_or(|t| name.tags.contains(t))
+      });
+      Ok(found)
+    }
+
+    fn get(&self, digest: &str) -> Result<Option<Box<dyn oci::Image>>> {
+      Ok(self.list()?.into_iter().find(|i| i.digest() == digest))
+    }
+  }
+
+  #[derive(Debug)]
+  struct CachedImage {
+    name: build::ImageName,
+    fingerprint: String,
+  }
+
+  impl oci::Image for CachedImage {
+    fn digest(&self) -> String {
+      format!("sha256:{}", self.name.image.len())
+    }
+
+    fn name(&self) -> build::ImageName {
+      self.name.clone()
+    }
+
+    fn created(&self) -> SystemTime {
+      SystemTime::UNIX_EPOCH
+    }
+
+    fn fingerprint(&self) -> Option<String> {
+      Some(self.fingerprint.clone())
+    }
+
+    fn platforms(&self) -> Result<HashSet<Arch>> {
+      Ok(HashSet::from([Arch::Amd64]))
+    }
+
+    fn attest(&self, _: &build::Provenance) -> Result<()> {
+      panic!("unexpected attestation of {}", self.name.image)
+    }
+
+    fn sign(&self, _: &oci::Signature) -> Result<()> {
+      panic!("unexpected signing of {}", self.name.image)
+    }
+
+    fn construct_new(&self, _: &oci::Settings) -> Box<dyn oci::Construction> {
+      panic!("unexpected construction on {}", self.name.image)
+    }
+  }
+
+  /// Signs each image with a signature named after the image, and resolves
+  /// it to a digest named the same.
+  #[derive(Debug)]
+  struct Signed {}
+
+  impl Signed {
+    fn name(image: &str) -> String {
+      let repository = image.split(':').next().unwrap_or_default();
+      match repository.rsplit_once('/') {
+        Some((_, "base")) => "trusted".to_string(),
+        Some((_, name)) => name.to_string(),
+        None => repository.to_string(),
+      }
+    }
+  }
+
+  impl oci::Registry for Signed {
+    fn fetch(
+      &self,
+      image: &str,
+      _: &HashSet<Arch>,
+      _: &(dyn Fn(usize, usize) + Sync),
+    ) -> Result<Box<dyn oci::Image>> {
+      panic!("unexpected fetch of {}", image)
+    }
+
+    fn platforms(&self, image: &str) -> Result<HashSet<Arch>> {
+      panic!("unexpected platforms lookup of {}", image)
+    }
+
+    fn digest(&self, image: &str) -> Result<String> {
+      Ok(Signed::name(image))
+    }
+
+    fn push_signature(
+      &self,
+      image: &str,
+      _: &str,
+      _: &oci::Signature,
+      _: oci::SignatureScheme,
+    ) -> Result<()> {
+      panic!("unexpected signature push of {}", image)
+    }
+
+    fn signatures(&self, image: &str, _: &str) -> Result<Vec<oci::Signature>> {
+      Ok(vec![oci::Signature {
+        payload: vec![],
+        signature: Signed::name(image),
+      }])
+    }
+  }
+
+  /// Trusts the signatures made for the "trusted" digest.
+  #[derive(Debug)]
+  struct Trusting {}
+
+  impl oci::Verifier for Trusting {
+    fn verify(&self, digest: &str, signature: &oci::Signature) -> bool {
+      digest == "trusted" && signature.signature == "trusted"
+    }
+  }
+}
@@ -1,15 +1,37 @@
 use std::fmt::{Display, Formatter};
 use std::result;
 
+use crate::policy;
+
 pub type Result<T> = result::Result<T, Error>;
 
 #[derive(Debug)]
 pub enum Error {
   Bug(String),
-  Unexpected(Box<dyn std::error::Error>),
+  Unexpected(Box<dyn std::error::Error + Send + Sync>),
   InvalidInput {
     message: String,
-    cause: Option<Box<dyn std::error::Error>>,
+    cause: Option<Box<dyn std::error::Error + Send + Sync>>,
+  },
+  NotReproducible {
+    first: String,
+    second: String,
+  },
+  /// Some builds of a batch failed. Each failure is reported on its own.
+  BatchFailed {
+    failed: usize,
+    total: usize,
+  },
+  /// No signature of the base image, by its bare manifest digest, verifies
+  /// against the trusted key.
+  UnverifiedBase {
+    image: String,
+    digest: String,
+  },
+  /// The image violates a rule of the trust policy.
+  PolicyViolation {
+    image: String,
+    rule: policy::Rule,
   },
 }
 
@@ -21,7 +43,9 @@ impl Error {
     }
   }
 
-  pub fn invalid_input_from(cause: Box<dyn std::error::Error>) -> Error {
+  pub fn invalid_input_from(
+    cause: Box<dyn std::error::Error + Send + Sync>,
+  ) -> Error {
     Error::InvalidInput {
       message: format!("{}", &cause),
       cause: Some(cause),
@@ -37,6 +61,24 @@ impl Display for Error {
         write!(f, "invalid input: {}", &message)
       }
       Error::Bug(msg) => write!(f, "bug: {}", msg),
+      Error::NotReproducible { first, second } => write!(
+        f,
+        "not reproducible: builds yield different digests: {} and {}",
+        first, second
+      ),
+      Error::BatchFailed { failed, total } => {
+        write!(f, "{} of {} builds failed", failed, total)
+      }
+      Error::UnverifiedBase { image, digest } => write!(
+        f,
+        "no signature of base image {} (sha256:{}) verifies against the key",
+        image, digest
+      ),
+      Error::PolicyViolation { image, rule } => write!(
+        f,
+        "image {} violates the trust policy rule: {}",
+        image, rule
+      ),
     }
   }
 }
@@ -56,6 +98,10 @@ impl std::error::Error for Error {
         Some(err) => Some(&***Box::from(err)),
       },
       Error::Bug(_) => None,
+      Error::NotReproducible { .. } => None,
+      Error::BatchFailed { .. } => None,
+      Error::UnverifiedBase { .. } => None,
+      Error::PolicyViolation { .. } => None,
     }
   }
 }
@@ -1,14 +1,45 @@
 use crate::{error, Artifact};
 use std::fmt::Debug;
 use std::io;
-use std::path::PathBuf;
-use std::time::SystemTime;
+use std::path::{Path, PathBuf};
 
-pub trait ArtifactResolver: Debug {
-  fn resolve(&self, ar: &Artifact) -> error::Result<Vec<PathBuf>>;
+pub trait ArtifactResolver: Debug + Send + Sync {
+  fn resolve(&self, ar: &Artifact) -> error::Result<Vec<ResolvedFile>>;
 }
 
-pub trait Files: Debug {
-  fn read(&self, p: &PathBuf) -> io::Result<Box<dyn io::Read>>;
-  fn modified(&self, p: &PathBuf) -> io::Result<SystemTime>;
+/// A regular file an artifact resolved to.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct ResolvedFile {
+  /// The path of the file on host.
+  pub path: PathBuf,
+  /// The path of the file relative to the artifact root: the directory given,
+  /// or the literal prefix of the glob. It's kept within a directory
+  /// destination.
+  pub relative: PathBuf,
+  /// Whether the artifact is a directory, which always lands in a directory.
+  pub from_dir: bool,
+}
+
+pub trait Files: Debug + Send + Sync {
+  fn read(&self, p: &Path) -> io::Result<Box<dyn io::Read>>;
+  /// Permission bits of the file on host.
+  fn mode(&self, p: &Path) -> io::Result<u32>;
+  /// Size of the file on host, in bytes.
+  fn size(&self, p: &Path) -> io::Result<u64>;
+  /// The target of the file, if it's a symlink.
+  fn symlink(&self, p: &Path) -> io::Result<Option<PathBuf>>;
+  /// Device and inode numbers, identifying the file on host, if known.
+  fn inode(&self, p: &Path) -> io::Result<Option<(u64, u64)>>;
+  /// Rust crates compiled into the file, as recorded by `cargo auditable`.
+  /// Empty, when the file isn't a binary carrying such a record.
+  fn packages(&self, p: &Path) -> io::Result<Vec<Package>>;
+}
+
+/// A package a binary was built from.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Package {
+  pub name: String,
+  pub version: String,
+  /// Where the package came from, like "crates.io", "git" or "local".
+  pub source: String,
 }
@@ -1,19 +1,113 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
 pub mod build;
 pub mod error;
 pub mod fs;
+pub mod observe;
 pub mod oci;
+pub mod policy;
+pub mod sign;
 
 #[derive(PartialEq, Eq, Debug, Hash, Clone)]
 pub struct Artifact {
   pub arch: Option<Arch>,
   pub from: String,
   pub to: Option<String>,
+  /// Permission bits of the files. When not given, the host ones are kept.
+  pub mode: Option<u32>,
+  /// Ownership of the files. When not given, the files are owned by root.
+  pub owner: Option<Owner>,
+  /// Whether host symlinks are kept as symlinks, instead of being followed.
+  pub keep_symlinks: bool,
+  /// Glob patterns of paths to leave out, relative to the walked directory.
+  /// An excluded directory isn't descended into.
+  pub exclude: Vec<String>,
+  /// Whether the `.gitignore` files leave out matches too, like the
+  /// `.ocilotignore` ones always do.
+  pub gitignore: bool,
+  /// A named layer to put the files into, shared with other artifacts naming
+  /// it. When not given, the layering of the build decides.
+  pub layer: Option<String>,
+}
+
+/// A symbolic link to create within the image, like `ln -s target path`.
+#[derive(PartialEq, Eq, Debug, Hash, Clone)]
+pub struct Symlink {
+  pub arch: Option<Arch>,
+  /// An absolute path of the link within the image.
+  pub path: String,
+  /// A path the link points to, kept as given.
+  pub target: String,
 }
 
+/// An owner of a file within the image, like in `chown user:group`.
 #[derive(PartialEq, Eq, Debug, Hash, Clone)]
+pub struct Owner {
+  pub user: Id,
+  /// When not given, the group matching the user is used.
+  pub group: Option<Id>,
+}
+
+/// A user, or a group, given by its numeric id or by its name. The names are
+/// resolved within the base image.
+#[derive(PartialEq, Eq, Debug, Hash, Clone)]
+pub enum Id {
+  Numeric(u32),
+  Name(String),
+}
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Hash, Clone)]
 pub enum Arch {
   Amd64,
   Arm64,
   Ppc64le,
   S390x,
 }
+
+impl Display for Arch {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    let name = match self {
+      Arch::Amd64 => "amd64",
+      Arch::Arm64 => "arm64",
+      Arch::Ppc64le => "ppc64le",
+      Arch::S390x => "s390x",
+    };
+    write!(f, "{}", name)
+  }
+}
+
+impl Display for Id {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Id::Numeric(id) => write!(f, "{}", id),
+      Id::Name(name) => write!(f, "{}", name),
+    }
+  }
+}
+
+impl Display for Owner {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match &self.group {
+      Some(group) => write!(f, "{}:{}", self.user, group),
+      None => write!(f, "{}", self.user),
+    }
+  }
+}
+
+impl FromStr for Arch {
+  type Err = error::Error;
+
+  /// Parses the architecture name as used by the OCI image spec.
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_lowercase().as_str() {
+      "amd64" => Ok(Arch::Amd64),
+      "arm64" => Ok(Arch::Arm64),
+      "ppc64le" => Ok(Arch::Ppc64le),
+      "s390x" => Ok(Arch::S390x),
+      other => Err(error::Error::invalid_input(
+        format!("unknown arch: {}", other).as_str(),
+      )),
+    }
+  }
+}
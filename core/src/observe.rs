@@ -0,0 +1,44 @@
+use std::fmt::Debug;
+
+use crate::build::ImageName;
+use crate::Arch;
+
+/// Receives the events of the builds, as they happen, to render their
+/// progress. Concurrent builds report from their own threads, so the events
+/// of different images interleave.
+pub trait BuildObserver: Debug + Send + Sync {
+  /// Notes the event of the build of the image.
+  fn observe(&self, image: &ImageName, event: &Event);
+}
+
+/// Something that happened during a build. More kinds of events may be added,
+/// so observers need to ignore the ones they don't know.
+#[derive(PartialEq, Eq, Debug, Clone)]
+#[non_exhaustive]
+pub enum Event {
+  /// The artifact, as given by the build, resolved to the host files.
+  ArtifactResolved { from: String, files: usize },
+  /// The base image started to be pulled from the registry. Bases shared by
+  /// builds are reported by the one pulling them.
+  BaseFetchStarted { image: String },
+  /// Some of the layers of the base got pulled. The total grows as the
+  /// manifests of its platforms get pulled, one after another.
+  BaseFetchProgress {
+    image: String,
+    pulled: usize,
+    total: usize,
+  },
+  /// The base image got pulled, by its bare manifest digest.
+  BaseFetchFinished { image: String, digest: String },
+  /// A layer got written, for the platform, or for a single platform image.
+  LayerWritten {
+    arch: Option<Arch>,
+    digest: String,
+    size: u64,
+  },
+  /// The image was built before, with the same fingerprint, and is taken
+  /// from the cache.
+  CacheHit { digest: String, fingerprint: String },
+  /// The manifest of the image got built, by its bare digest.
+  ManifestBuilt { digest: String },
+}
@@ -1,36 +1,209 @@
 use crate::error::Result;
-use crate::{build, Arch};
-use std::collections::HashSet;
+use crate::{build, Arch, Owner};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::fmt::Debug;
 use std::path::PathBuf;
 use std::{io, time};
 
-pub trait Config: Debug {
+pub trait Config: Debug + Send + Sync {
   fn workdir(&self) -> Result<PathBuf>;
 }
 
-pub trait Registry: Debug {
-  fn fetch(&self, image: &String) -> Result<Box<dyn Image>>;
+pub trait Registry: Debug + Send + Sync {
+  /// Fetches the image, for the given architectures. When many
+  /// architectures are given, the image needs to be a multi-platform one.
+  /// The progress is given the layers pulled so far, out of the ones of the
+  /// platform manifests pulled so far.
+  fn fetch(
+    &self,
+    image: &str,
+    archs: &HashSet<Arch>,
+    progress: &(dyn Fn(usize, usize) + Sync),
+  ) -> Result<Box<dyn Image>>;
+  /// Lists the architectures the image is offered for, without pulling.
+  fn platforms(&self, image: &str) -> Result<HashSet<Arch>>;
+  /// Resolves the image reference to its manifest digest, without pulling.
+  fn digest(&self, image: &str) -> Result<String>;
+  /// Pushes the signature of the image, by its bare manifest digest, to the
+  /// image repository.
+  fn push_signature(
+    &self,
+    image: &str,
+    digest: &str,
+    signature: &Signature,
+    scheme: SignatureScheme,
+  ) -> Result<()>;
+  /// Lists the signatures pushed for the image, by its bare manifest digest,
+  /// under the "sha256-<hex>.sig" tag. Empty, when there are none.
+  fn signatures(&self, image: &str, digest: &str) -> Result<Vec<Signature>>;
 }
 
-pub trait Cache: Debug {
+pub trait Cache: Debug + Send + Sync {
   fn list(&self) -> Result<Vec<Box<dyn Image>>>;
+  /// Finds the cached images of the name, having the tag, when one is given.
+  fn find(&self, image: &str, tag: Option<&str>)
+    -> Result<Vec<Box<dyn Image>>>;
+  /// Finds the cached image of the manifest digest, bare or not.
+  fn get(&self, digest: &str) -> Result<Option<Box<dyn Image>>>;
 }
 
-pub trait Image: Debug {
+pub trait Image: Debug + Send + Sync {
   fn digest(&self) -> String;
+  /// The manifest digest the image was fetched by: its own, unless it's a
+  /// platform image picked from a multi-platform one, fetched by the digest
+  /// of its index.
+  fn fetched_digest(&self) -> String {
+    self.digest()
+  }
   fn name(&self) -> build::ImageName;
   fn created(&self) -> time::SystemTime;
-  fn construct_new(&self, archs: &HashSet<Arch>) -> Box<dyn Construction>;
+  /// The fingerprint of the build that produced this image, if any.
+  fn fingerprint(&self) -> Option<String>;
+  /// Lists the architectures the image offers.
+  fn platforms(&self) -> Result<HashSet<Arch>>;
+  /// Records the provenance of the image, next to it, along with its bill of
+  /// materials, when the build asks for one. They aren't attached to the
+  /// image in a registry yet.
+  fn attest(&self, provenance: &build::Provenance) -> Result<()>;
+  /// Records the signature of the image next to it, to be pushed along it
+  /// once publishing is implemented.
+  fn sign(&self, signature: &Signature) -> Result<()>;
+  fn construct_new(&self, settings: &Settings) -> Box<dyn Construction>;
+}
+
+/// Signs images with a key at hand, the way cosign does.
+pub trait Signer: Debug + Send + Sync {
+  /// Signs a simple signing payload, naming the image and its bare manifest
+  /// digest.
+  fn sign(&self, image: &str, digest: &str) -> Result<Signature>;
+}
+
+/// Verifies signatures of images against a trusted key.
+pub trait Verifier: Debug + Send + Sync {
+  /// Whether the signature is a valid one, of a simple signing payload naming
+  /// the image bare manifest digest.
+  fn verify(&self, digest: &str, signature: &Signature) -> bool;
+}
+
+/// A cosign compatible signature of an image.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Signature {
+  /// The simple signing payload, in JSON, as signed.
+  pub payload: Vec<u8>,
+  /// The signature of the payload, in base64.
+  pub signature: String,
+}
+
+/// How a signature is attached to the image within a registry.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub enum SignatureScheme {
+  /// A manifest tagged after the image digest, as "sha256-<hex>.sig".
+  #[default]
+  Tag,
+  /// An OCI 1.1 referrer, having the image as its subject.
+  Referrer,
+}
+
+/// Describes how a new image should be constructed.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Settings {
+  pub archs: HashSet<Arch>,
+  /// A creation time of the image and its files. When not given, the current
+  /// time is used, which makes the build not reproducible.
+  pub created: Option<time::SystemTime>,
+  pub config: ConfigChanges,
+  /// Tells the current time, whenever the construction needs it.
+  pub clock: Clock,
+}
+
+/// The source of the current time of the constructions. A reproducibility
+/// check skews it, so a time leaking into an image shows.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct Clock {
+  /// How far ahead of the actual time the one told is.
+  pub skew: time::Duration,
+}
+
+impl Clock {
+  pub fn now(&self) -> time::SystemTime {
+    time::SystemTime::now() + self.skew
+  }
+}
+
+/// Changes to the runtime config of the image, applied on top of the one
+/// inherited from the base image.
+#[derive(PartialEq, Eq, Debug, Clone, Default, Hash)]
+pub struct ConfigChanges {
+  pub entrypoint: Change<Vec<String>>,
+  pub cmd: Change<Vec<String>>,
+  pub env: Change<Vec<(String, String)>>,
+  pub workdir: Change<String>,
+  pub user: Change<String>,
+  pub labels: Change<BTreeMap<String, String>>,
+  /// Ports in form of "port/protocol", like "8080/tcp".
+  pub exposed_ports: Change<BTreeSet<String>>,
+  pub volumes: Change<BTreeSet<String>>,
+  pub stop_signal: Change<String>,
+}
+
+/// How a config value of the new image relates to the base image one.
+#[derive(PartialEq, Eq, Debug, Clone, Default, Hash)]
+pub enum Change<T> {
+  /// The base image value is kept.
+  #[default]
+  Inherit,
+  /// The base image value is dropped.
+  Reset,
+  /// The value replaces the base image one.
+  Set(T),
+  /// The value is merged into the base image one. Entries with the same key
+  /// are replaced.
+  Append(T),
 }
 
 pub trait Construction {
-  fn add(&self, files: Vec<Input>);
-  fn build(&self, named: &build::ImageName) -> Result<Box<dyn Image>>;
+  /// Adds the files as a new layer, on top of the ones added before. Gives
+  /// the layers written, one per platform getting any of the files.
+  fn add(&mut self, files: Vec<Input>) -> Result<Vec<LayerInfo>>;
+  fn build(
+    &self,
+    named: &build::ImageName,
+    fingerprint: &str,
+  ) -> Result<Box<dyn Image>>;
 }
 
+/// A layer written by a construction.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct LayerInfo {
+  /// The platform the layer is written for. None, for a single platform
+  /// image.
+  pub arch: Option<Arch>,
+  /// The bare digest of the compressed layer.
+  pub digest: String,
+  /// The size of the compressed layer, in bytes.
+  pub size: u64,
+}
+
+/// A single file to be placed into the image.
 pub struct Input {
   pub arch: Option<Arch>,
   pub from: Box<dyn io::Read>,
-  pub to: Option<String>,
+  /// An absolute path of the file within the image.
+  pub to: String,
+  /// Permission bits of the file within the image.
+  pub mode: u32,
+  /// Ownership of the file within the image. When not given, root owns it.
+  pub owner: Option<Owner>,
+  /// Makes the file a link, with no data of its own.
+  pub link: Option<Link>,
+}
+
+/// A link placed into the image, instead of a regular file.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Link {
+  /// A symbolic link, pointing to the path, as given.
+  Symbolic(String),
+  /// A hard link to another file, by its absolute path within the image.
+  /// The file needs to be placed earlier, within the same layer.
+  Hard(String),
 }
@@ -0,0 +1,305 @@
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+
+use crate::build::ImageName;
+use crate::error::{Error, Result};
+use crate::oci;
+
+const DOCKER_REGISTRY: &str = "docker.io";
+const LATEST: &str = "latest";
+
+/// A trust policy, enforced on every base image pulled, and every image signed
+/// before its signature is pushed. The default one allows anything.
+#[derive(Debug, Default)]
+pub struct Policy {
+  /// Scopes the images may come from, or be signed within: registries, like
+  /// "quay.io", or repositories, like "quay.io/acme". Any, when empty.
+  pub allowed: Vec<String>,
+  /// Whether the base images need to be pinned by digest.
+  pub require_digest: bool,
+  /// Whether the "latest" tag is refused, even when implied.
+  pub forbid_latest: bool,
+  /// Scopes whose images need a valid signature. The most specific scope of
+  /// an image applies.
+  pub signed: Vec<SignedScope>,
+}
+
+/// A scope whose images need a signature, verifying against any of its keys.
+#[derive(Debug)]
+pub struct SignedScope {
+  pub scope: String,
+  pub verifiers: Vec<Box<dyn oci::Verifier>>,
+}
+
+/// A rule of the policy an image violates.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Rule {
+  Allowed,
+  RequireDigest,
+  ForbidLatest,
+  /// The signatures required within the scope.
+  Signed(String),
+}
+
+impl Display for Rule {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Rule::Allowed => write!(f, "allowed"),
+      Rule::RequireDigest => write!(f, "require-digest"),
+      Rule::ForbidLatest => write!(f, "forbid-latest"),
+      Rule::Signed(scope) => write!(f, "signatures of {}", scope),
+    }
+  }
+}
+
+impl Policy {
+  /// Checks the reference of a base image, before it's pulled.
+  pub fn check_base(&self, image: &str) -> Result<()> {
+    let reference = Reference::parse(image);
+    if !self.allows(&reference.repository) {
+      return Err(violation(image, Rule::Allowed));
+    }
+    if self.require_digest && reference.digest.is_none() {
+      return Err(violation(image, Rule::RequireDigest));
+    }
+    let latest = match reference.tag {
+      Some(tag) => tag == LATEST,
+      None => reference.digest.is_none(),
+    };
+    if self.forbid_latest && latest {
+      return Err(violation(image, Rule::ForbidLatest));
+    }
+    Ok(())
+  }
+
+  /// Checks the image to publish, with its tags, and whether it's signed.
+  pub fn check_publish(&self, name: &ImageName, signed: bool) -> Result<()> {
+    let repository = Reference::parse(&name.image).repository;
+    if !self.allows(&repository) {
+      return Err(violation(&name.image, Rule::Allowed));
+    }
+    let latest = name.tags.is_empty() || name.tags.contains(LATEST);
+    if self.forbid_latest && latest {
+      return Err(violation(&name.image, Rule::ForbidLatest));
+    }
+    match self.signed_scope(&name.image) {
+      Some(scope) if !signed => {
+        Err(violation(&name.image, Rule::Signed(scope.scope.clone())))
+      }
+      _ => Ok(()),
+    }
+  }
+
+  /// Checks the image before its signature, of the bare manifest digest, is
+  /// pushed under the "sha256-<hex>.sig" tag of its repository.
+  pub fn check_signature(&self, image: &str, digest: &str) -> Result<()> {
+    let name = ImageName {
+      image: Reference::parse(image).repository,
+      tags: HashSet::from([format!("sha256-{}.sig", digest)]),
+    };
+    self.check_publish(&name, true)
+  }
+
+  /// The most specific scope requiring signatures of the image, if any.
+  pub fn signed_scope(&self, image: &str) -> Option<&SignedScope> {
+    let repository = Reference::parse(image).repository;
+    self
+      .signed
+      .iter()
+      .filter(|s| within(&repository, &s.scope))
+      .max_by_key(|s| s.scope.len())
+  }
+
+  fn allows(&self, repository: &str) -> bool {
+    self.allowed.is_empty()
+      || self.allowed.iter().any(|scope| within(repository, scope))
+  }
+}
+
+impl SignedScope {
+  /// Whether any of the signatures verifies against any of the keys.
+  pub fn verifies(&self, digest: &str, signatures: &[oci::Signature]) -> bool {
+    signatures
+      .iter()
+      .any(|s| self.verifiers.iter().any(|v| v.verify(digest, s)))
+  }
+}
+
+fn violation(image: &str, rule: Rule) -> Error {
+  Error::PolicyViolation {
+    image: image.to_string(),
+    rule,
+  }
+}
+
+/// Whether the repository is the scope, or is nested in it.
+fn within(repository: &str, scope: &str) -> bool {
+  let scope = scope.trim_end_matches('/');
+  repository == scope
+    || repository
+      .strip_prefix(scope)
+      .is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// An image reference, split into its parts. Short names resolve to
+/// docker.io, like they do when pulled.
+#[derive(PartialEq, Eq, Debug)]
+struct Reference<'a> {
+  repository: String,
+  tag: Option<&'a str>,
+  digest: Option<&'a str>,
+}
+
+impl Reference<'_> {
+  fn parse(image: &str) -> Reference<'_> {
+    let (name, digest) = match image.split_once('@') {
+      Some((name, digest)) => (name, Some(digest)),
+      None => (image, None),
+    };
+    let (name, tag) = match name.rsplit_once(':') {
+      Some((name, tag)) if !tag.contains('/') => (name, Some(tag)),
+      _ => (name, None),
+    };
+    let repository = match name.split_once('/') {
+      Some((host, _))
+        if host.contains('.') || host.contains(':') || host == "localhost" =>
+      {
+        name.to_string()
+      }
+      Some(_) => format!("{}/{}", DOCKER_REGISTRY, name),
+      None => format!("{}/library/{}", DOCKER_REGISTRY, name),
+    };
+    Reference {
+      repository,
+      tag,
+      digest,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::HashSet;
+
+  use crate::build::ImageName;
+  use crate::error::Error;
+  use crate::oci;
+  use crate::policy::{Policy, Reference, Rule, SignedScope};
+
+  #[test]
+  fn references() {
+    let parsed = |image| {
+      let r = Reference::parse(image);
+      (r.repository, r.tag, r.digest)
+    };
+    assert_eq!(
+      parsed("nginx"),
+      ("docker.io/library/nginx".to_string(), None, None)
+    );
+    assert_eq!(
+      parsed("acme/app:v1"),
+      ("docker.io/acme/app".to_string(), Some("v1"), None)
+    );
+    assert_eq!(
+      parsed("localhost:5000/app@sha256:abc"),
+      ("localhost:5000/app".to_string(), None, Some("sha256:abc"))
+    );
+  }
+
+  #[test]
+  fn base_rules() {
+    let policy = Policy {
+      allowed: vec!["gcr.io/distroless".to_string(), "quay.io".to_string()],
+      require_digest: false,
+      forbid_latest: true,
+      signed: vec![],
+    };
+    let rule = |image| match policy.check_base(image) {
+      Ok(()) => None,
+      Err(Error::PolicyViolation { rule, .. }) => Some(rule),
+      Err(err) => panic!("{:?}", err),
+    };
+
+    assert_eq!(rule("quay.io/acme/app:v1"), None);
+    assert_eq!(rule("gcr.io/distroless/static@sha256:abc"), None);
+    assert_eq!(
+      rule("gcr.io/distroless-fork/static:v1"),
+      Some(Rule::Allowed)
+    );
+    assert_eq!(rule("nginx:1.23"), Some(Rule::Allowed));
+    assert_eq!(rule("quay.io/acme/app"), Some(Rule::ForbidLatest));
+    assert_eq!(rule("quay.io/acme/app:latest"), Some(Rule::ForbidLatest));
+
+    let policy = Policy {
+      require_digest: true,
+      ..Default::default()
+    };
+    assert!(policy.check_base("nginx@sha256:abc").is_ok());
+    assert!(matches!(
+      policy.check_base("nginx:1.23"),
+      Err(Error::PolicyViolation {
+        rule: Rule::RequireDigest,
+        ..
+      })
+    ));
+  }
+
+  #[test]
+  fn signed_scopes() {
+    let scope = |scope: &str| SignedScope {
+      scope: scope.to_string(),
+      verifiers: vec![Box::new(Rejecting {})],
+    };
+    let policy = Policy {
+      signed: vec![scope("quay.io"), scope("quay.io/acme")],
+      ..Default::default()
+    };
+    let name = |image: &str| ImageName {
+      image: image.to_string(),
+      tags: HashSet::from(["v1".to_string()]),
+    };
+
+    let got = policy.signed_scope("quay.io/acme/app:v1").map(|s| &s.scope);
+    assert_eq!(got.map(String::as_str), Some("quay.io/acme"));
+    assert!(policy.signed_scope("gcr.io/distroless/static").is_none());
+    assert!(policy
+      .check_publish(&name("quay.io/acme/app"), true)
+      .is_ok());
+    assert!(policy
+      .check_publish(&name("ghcr.io/acme/app"), false)
+      .is_ok());
+    match policy.check_publish(&name("quay.io/other/app"), false) {
+      Err(Error::PolicyViolation { rule, .. }) => {
+        assert_eq!(rule, Rule::Signed("quay.io".to_string()))
+      }
+      got => panic!("{:?}", got),
+    }
+  }
+
+  #[test]
+  fn signature_rules() {
+    let policy = Policy {
+      allowed: vec!["quay.io/acme".to_string()],
+      forbid_latest: true,
+      ..Default::default()
+    };
+
+    assert!(policy.check_signature("quay.io/acme/app", "abc").is_ok());
+    match policy.check_signature("ghcr.io/acme/app:v1", "abc") {
+      Err(Error::PolicyViolation { image, rule }) => {
+        assert_eq!(image, "ghcr.io/acme/app");
+        assert_eq!(rule, Rule::Allowed);
+      }
+      got => panic!("{:?}", got),
+    }
+  }
+
+  #[derive(Debug)]
+  struct Rejecting {}
+
+  impl oci::Verifier for Rejecting {
+    fn verify(&self, _: &str, _: &oci::Signature) -> bool {
+      false
+    }
+  }
+}
@@ -0,0 +1,31 @@
+use tracing::{debug, instrument};
+
+use crate::error::Result;
+use crate::oci::{Registry, SignatureScheme, Signer};
+use crate::policy::Policy;
+
+/// Signs images already pushed to a registry.
+#[derive(Debug)]
+pub struct Command {
+  pub registry: Box<dyn Registry>,
+  pub signer: Box<dyn Signer>,
+  /// The trust policy the signed images have to comply with.
+  pub policy: Policy,
+}
+
+impl Command {
+  /// Signs the image by its manifest digest, and pushes the signature to the
+  /// image repository, unless the policy forbids it. Returns the signed
+  /// digest.
+  #[instrument(ret, level = "trace")]
+  pub fn sign(&self, image: &str, scheme: SignatureScheme) -> Result<String> {
+    let digest = self.registry.digest(image)?;
+    self.policy.check_signature(image, &digest)?;
+    let signature = self.signer.sign(image, &digest)?;
+    debug!(image = ?image, digest = ?digest, "Image signed");
+    self
+      .registry
+      .push_signature(image, &digest, &signature, scheme)?;
+    Ok(digest)
+  }
+}
@@ -0,0 +1,348 @@
+use std::fs::File;
+use std::io;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+use flate2::read::ZlibDecoder;
+use ocilot_core::fs::Package;
+use serde::Deserialize;
+
+/// The ELF section `cargo auditable` embeds the dependency tree into, as
+/// a zlib compressed JSON.
+const SECTION: &[u8] = b".dep-v0";
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+/// The compressed record is capped to a few megabytes, guarding against
+/// malformed binaries.
+const MAX_SECTION_SIZE: u64 = 8 << 20;
+/// The decompressed record is capped too, guarding against zlib bombs.
+const MAX_RECORD_SIZE: u64 = 16 << 20;
+
+#[derive(Deserialize)]
+struct VersionInfo {
+  packages: Vec<AuditablePackage>,
+}
+
+#[derive(Deserialize)]
+struct AuditablePackage {
+  name: String,
+  version: String,
+  source: String,
+  #[serde(default)]
+  kind: Option<String>,
+}
+
+/// Reads the crates recorded by `cargo auditable` in an ELF binary. Build
+/// dependencies are left out, as they don't end up in the binary. Files which
+/// aren't ELF, or carry no record, have no packages.
+pub(crate) fn packages(p: &Path) -> io::Result<Vec<Package>> {
+  let mut file = File::open(p)?;
+  let section = match find_section(&mut file)? {
+    Some(section) => section,
+    None => return Ok(vec![]),
+  };
+  let mut json = Vec::new();
+  ZlibDecoder::new(section.as_slice())
+    .take(MAX_RECORD_SIZE + 1)
+    .read_to_end(&mut json)?;
+  if json.len() as u64 > MAX_RECORD_SIZE {
+    return Err(invalid_data(format!(
+      "ELF section decompresses to more than {} bytes",
+      MAX_RECORD_SIZE
+    )));
+  }
+  let info: VersionInfo = serde_json::from_slice(&json)
+    .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+  Ok(
+    info
+      .packages
+      .into_iter()
+      .filter(|p| p.kind.as_deref() != Some("build"))
+      .map(|p| Package {
+        name: p.name,
+        version: p.version,
+        source: p.source,
+      })
+      .collect(),
+  )
+}
+
+/// The layout of the ELF header and its section headers, for either class.
+struct Elf {
+  wide: bool,
+  little: bool,
+}
+
+impl Elf {
+  fn word(&self, buf: &[u8], at: usize) -> u64 {
+    if self.wide {
+      let b: [u8; 8] = buf[at..at + 8].try_into().unwrap();
+      if self.little {
+        u64::from_le_bytes(b)
+      } else {
+        u64::from_be_bytes(b)
+      }
+    } else {
+      self.u32(buf, at) as u64
+    }
+  }
+
+  fn u32(&self, buf: &[u8], at: usize) -> u32 {
+    let b: [u8; 4] = buf[at..at + 4].try_into().unwrap();
+    if self.little {
+      u32::from_le_bytes(b)
+    } else {
+      u32::from_be_bytes(b)
+    }
+  }
+
+  fn u16(&self, buf: &[u8], at: usize) -> u16 {
+    let b: [u8; 2] = buf[at..at + 2].try_into().unwrap();
+    if self.little {
+      u16::from_le_bytes(b)
+    } else {
+      u16::from_be_bytes(b)
+    }
+  }
+
+  /// Offsets of the name, file offset and size within a section header.
+  fn section_fields(&self) -> (usize, usize, usize) {
+    if self.wide {
+      (0x00, 0x18, 0x20)
+    } else {
+      (0x00, 0x10, 0x14)
+    }
+  }
+}
+
+fn find_section(file: &mut File) -> io::Result<Option<Vec<u8>>> {
+  let mut header = [0u8; 64];
+  let read = read_up_to(file, &mut header)?;
+  if read < 52 || !header.starts_with(ELF_MAGIC) {
+    return Ok(None);
+  }
+  let elf = match (header[4], header[5]) {
+    (1, 1) => Elf {
+      wide: false,
+      little: true,
+    },
+    (1, 2) => Elf {
+      wide: false,
+      little: false,
+    },
+    (2, 1) => Elf {
+      wide: true,
+      little: true,
+    },
+    (2, 2) => Elf {
+      wide: true,
+      little: false,
+    },
+    _ => return Ok(None),
+  };
+  if elf.wide && read < 64 {
+    return Ok(None);
+  }
+  let (shoff, entsize, num, strndx) = if elf.wide {
+    (
+      elf.word(&header, 0x28),
+      elf.u16(&header, 0x3a),
+      elf.u16(&header, 0x3c),
+      elf.u16(&header, 0x3e),
+    )
+  } else {
+    (
+      elf.word(&header, 0x20),
+      elf.u16(&header, 0x2e),
+      elf.u16(&header, 0x30),
+      elf.u16(&header, 0x32),
+    )
+  };
+  let (entsize, num) = (entsize as usize, num as usize);
+  if shoff == 0 || num == 0 {
+    return Ok(None);
+  }
+  if entsize != if elf.wide { 64 } else { 40 } || strndx as usize >= num {
+    return Err(invalid_data(format!(
+      "malformed ELF section headers: {} of {} bytes",
+      num, entsize
+    )));
+  }
+  let len = file.metadata()?.len();
+  let headers = read_at(file, len, shoff, (entsize * num) as u64)?;
+  let (name_at, offset_at, size_at) = elf.section_fields();
+  let section = |i: usize| {
+    let h = &headers[i * entsize..(i + 1) * entsize];
+    (
+      elf.u32(h, name_at) as usize,
+      elf.word(h, offset_at),
+      elf.word(h, size_at),
+    )
+  };
+  let (_, names_offset, names_size) = section(strndx as usize);
+  let names = read_at(file, len, names_offset, names_size)?;
+  for i in 0..num {
+    let (name, offset, size) = section(i);
+    let name = names.get(name..).unwrap_or_default();
+    let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(0)];
+    if name == SECTION {
+      return read_at(file, len, offset, size).map(Some);
+    }
+  }
+  Ok(None)
+}
+
+/// Reads a part of the file of the given length, which it has to be within.
+fn read_at(
+  file: &mut File,
+  len: u64,
+  offset: u64,
+  size: u64,
+) -> io::Result<Vec<u8>> {
+  if size > MAX_SECTION_SIZE {
+    return Err(invalid_data(format!(
+      "ELF section of {} bytes is too large",
+      size
+    )));
+  }
+  if offset.checked_add(size).is_none_or(|end| end > len) {
+    return Err(invalid_data(format!(
+      "ELF section at {} of {} bytes is past the end of file",
+      offset, size
+    )));
+  }
+  let mut buf = vec![0u8; size as usize];
+  file.seek(SeekFrom::Start(offset))?;
+  file.read_exact(&mut buf)?;
+  Ok(buf)
+}
+
+fn invalid_data(message: String) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Fills the buffer as far as the file allows, returning the bytes read.
+fn read_up_to(file: &mut File, buf: &mut [u8]) -> io::Result<usize> {
+  let mut read = 0;
+  while read < buf.len() {
+    match file.read(&mut buf[read..])? {
+      0 => break,
+      n => read += n,
+    }
+  }
+  Ok(read)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::Write;
+  use std::path::{Path, PathBuf};
+
+  use flate2::write::ZlibEncoder;
+  use flate2::Compression;
+
+  use crate::auditable::{packages, MAX_RECORD_SIZE};
+
+  const RECORD: &str = r#"{"packages":[
+    {"name":"acme","version":"0.1.0","source":"local","kind":"runtime",
+     "dependencies":[1,2],"root":true},
+    {"name":"serde","version":"1.0.193","source":"crates.io"},
+    {"name":"cc","version":"1.0.83","source":"crates.io","kind":"build"}
+  ]}"#;
+
+  fn compressed(data: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+  }
+
+  /// A little-endian ELF binary of the class, with the sections named
+  /// `.shstrtab` and `.dep-v0`, the latter carrying the data.
+  fn elf(wide: bool, data: &[u8]) -> Vec<u8> {
+    let names = b"\0.shstrtab\0.dep-v0\0";
+    let (header, entsize) = if wide { (64, 64) } else { (52, 40) };
+    let names_at = header;
+    let data_at = names_at + names.len();
+    let shoff = data_at + data.len();
+    let mut elf = vec![0u8; shoff + 3 * entsize];
+    elf[..4].copy_from_slice(b"\x7fELF");
+    elf[4] = if wide { 2 } else { 1 };
+    elf[5] = 1;
+    let put = |elf: &mut Vec<u8>, at: usize, value: usize, width: usize| {
+      elf[at..at + width].copy_from_slice(&value.to_le_bytes()[..width]);
+    };
+    let word = if wide { 8 } else { 4 };
+    let (shoff_at, entsize_at) = if wide { (0x28, 0x3a) } else { (0x20, 0x2e) };
+    put(&mut elf, shoff_at, shoff, word);
+    put(&mut elf, entsize_at, entsize, 2);
+    put(&mut elf, entsize_at + 2, 3, 2);
+    put(&mut elf, entsize_at + 4, 1, 2);
+    elf[names_at..data_at].copy_from_slice(names);
+    elf[data_at..shoff].copy_from_slice(data);
+    let (offset_at, size_at) = if wide { (0x18, 0x20) } else { (0x10, 0x14) };
+    for (i, (name, offset, size)) in
+      [(1, names_at, names.len()), (11, data_at, data.len())]
+        .into_iter()
+        .enumerate()
+    {
+      let at = shoff + (i + 1) * entsize;
+      put(&mut elf, at, name, 4);
+      put(&mut elf, at + offset_at, offset, word);
+      put(&mut elf, at + size_at, size, word);
+    }
+    elf
+  }
+
+  fn file(name: &str, content: &[u8]) -> PathBuf {
+    let dir = std::env::temp_dir().join("ocilot-auditable");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    std::fs::write(&path, content).unwrap();
+    path
+  }
+
+  fn names(path: &Path) -> Vec<String> {
+    packages(path)
+      .unwrap()
+      .into_iter()
+      .map(|p| format!("{}@{} {}", p.name, p.version, p.source))
+      .collect()
+  }
+
+  #[test]
+  fn elf_classes() {
+    let record = compressed(RECORD.as_bytes());
+    let want = vec!["acme@0.1.0 local", "serde@1.0.193 crates.io"];
+
+    assert_eq!(names(&file("elf64", &elf(true, &record))), want);
+    assert_eq!(names(&file("elf32", &elf(false, &record))), want);
+  }
+
+  #[test]
+  fn not_auditable() {
+    let script = file("script", b"#!/bin/sh\necho acme\n");
+    let short = file("short", b"\x7fELF\x02\x01");
+
+    assert!(names(&script).is_empty());
+    assert!(names(&short).is_empty());
+  }
+
+  #[test]
+  fn malformed() {
+    let record = compressed(RECORD.as_bytes());
+    let whole = elf(true, &record);
+    let truncated = file("truncated", &whole[..whole.len() - 10]);
+    let mut resized = whole.clone();
+    resized[0x3a] = 0xff;
+    let resized = file("resized", &resized);
+    let mut displaced = whole;
+    displaced[0x28..0x30].copy_from_slice(&u64::MAX.to_le_bytes());
+    let displaced = file("displaced", &displaced);
+    let bomb = vec![b' '; MAX_RECORD_SIZE as usize + 1];
+    let bomb = file("bomb", &elf(true, &compressed(&bomb)));
+
+    for path in [truncated, resized, displaced, bomb] {
+      let res = packages(&path);
+      assert!(res.is_err(), "{:?}: {:?}", path, res);
+    }
+  }
+}
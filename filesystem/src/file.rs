@@ -1,10 +1,9 @@
 use std::fmt::Debug;
 use std::fs::File;
-use std::path::PathBuf;
-use std::time::SystemTime;
+use std::path::{Path, PathBuf};
 use std::{fs, io};
 
-use ocilot_core::fs::Files;
+use ocilot_core::fs::{Files, Package};
 use tracing::instrument;
 
 #[derive(Debug)]
@@ -12,18 +11,70 @@ pub struct LocalFileSystem {}
 
 impl Files for LocalFileSystem {
   #[instrument(level = "trace")]
-  fn read(&self, p: &PathBuf) -> io::Result<Box<dyn io::Read>> {
+  fn read(&self, p: &Path) -> io::Result<Box<dyn io::Read>> {
     File::options()
       .read(true)
       .write(false)
       .create(false)
-      .open(p.as_path())
+      .open(p)
       .map(|f| Box::new(f) as Box<dyn io::Read>)
   }
 
   #[instrument(ret, level = "trace")]
-  fn modified(&self, p: &PathBuf) -> io::Result<SystemTime> {
+  fn mode(&self, p: &Path) -> io::Result<u32> {
     let md = fs::metadata(p)?;
-    md.modified()
+    Ok(permission_bits(&md))
+  }
+
+  #[instrument(ret, level = "trace")]
+  fn size(&self, p: &Path) -> io::Result<u64> {
+    fs::metadata(p).map(|md| md.len())
+  }
+
+  #[instrument(ret, level = "trace")]
+  fn symlink(&self, p: &Path) -> io::Result<Option<PathBuf>> {
+    let md = fs::symlink_metadata(p)?;
+    if md.file_type().is_symlink() {
+      fs::read_link(p).map(Some)
+    } else {
+      Ok(None)
+    }
+  }
+
+  #[instrument(ret, level = "trace")]
+  fn inode(&self, p: &Path) -> io::Result<Option<(u64, u64)>> {
+    let md = fs::metadata(p)?;
+    Ok(inode(&md))
+  }
+
+  #[instrument(ret, level = "trace")]
+  fn packages(&self, p: &Path) -> io::Result<Vec<Package>> {
+    crate::auditable::packages(p)
+  }
+}
+
+#[cfg(unix)]
+fn permission_bits(md: &fs::Metadata) -> u32 {
+  use std::os::unix::fs::PermissionsExt;
+  md.permissions().mode() & 0o7777
+}
+
+#[cfg(unix)]
+fn inode(md: &fs::Metadata) -> Option<(u64, u64)> {
+  use std::os::unix::fs::MetadataExt;
+  Some((md.dev(), md.ino()))
+}
+
+#[cfg(not(unix))]
+fn inode(_: &fs::Metadata) -> Option<(u64, u64)> {
+  None
+}
+
+#[cfg(not(unix))]
+fn permission_bits(md: &fs::Metadata) -> u32 {
+  if md.permissions().readonly() {
+    0o444
+  } else {
+    0o644
   }
 }
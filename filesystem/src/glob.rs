@@ -1,62 +1,294 @@
-use std::io;
+use std::fs;
 use std::path::{Path, PathBuf};
-use tracing::instrument;
+use tracing::{instrument, warn};
 
+use ignore::WalkBuilder;
 use ocilot_core as core;
 use ocilot_core::error::{Error, Result};
+use ocilot_core::fs::ResolvedFile;
 use wax;
 use wax::{Glob, GlobError};
 
+/// The name of the files listing paths to leave out of artifacts, with the
+/// `.gitignore` syntax. Looked up in the walked directories and their parents.
+pub const IGNORE_FILENAME: &str = ".ocilotignore";
+
 #[derive(Debug)]
 pub struct ArtifactResolver {}
 
 impl core::fs::ArtifactResolver for ArtifactResolver {
   #[instrument(ret, level = "trace")]
-  fn resolve(&self, art: &core::Artifact) -> Result<Vec<PathBuf>> {
+  fn resolve(&self, art: &core::Artifact) -> Result<Vec<ResolvedFile>> {
     let from = art.from.as_str();
     let path = Path::new(from);
-    if path.exists() && path.is_file() {
-      return Ok(vec![path.to_path_buf()]);
+    let keep = art.keep_symlinks;
+    if is_file(path, keep) {
+      return Ok(vec![ResolvedFile {
+        path: path.to_path_buf(),
+        relative: path.file_name().map(PathBuf::from).unwrap_or_default(),
+        from_dir: false,
+      }]);
+    }
+    if is_dir(path, keep) {
+      return walk(path, None, art, true);
     }
 
-    match wax::Glob::new(from) {
-      Ok(glob) => match resolve_glob(glob) {
-        Ok(paths) => Ok(paths),
-        Err(err) => Err(Error::Unexpected(Box::new(err))),
-      },
-      Err(gerr) => match gerr {
-        GlobError::Parse(perr) => Err(parse_error_as_core(perr)),
-        GlobError::Rule(rerr) => Err(rule_error_as_core(rerr)),
-        GlobError::Walk(werr) => Err(Error::Unexpected(Box::new(werr))),
-        _ => panic!("can't get here"),
-      },
+    match wax::Glob::partitioned(from) {
+      Ok((prefix, glob)) => {
+        let root = std::env::current_dir()?.join(prefix);
+        if !root.is_dir() {
+          return Ok(vec![]);
+        }
+        walk(&root, Some(&glob), art, false)
+      }
+      Err(gerr) => Err(glob_error_as_core(gerr)),
     }
   }
 }
 
+fn glob_error_as_core(err: GlobError) -> Error {
+  match err {
+    GlobError::Parse(perr) => parse_error_as_core(perr),
+    GlobError::Rule(rerr) => rule_error_as_core(rerr),
+    GlobError::Walk(werr) => Error::Unexpected(Box::new(werr)),
+    _ => panic!("can't get here"),
+  }
+}
+
 fn parse_error_as_core(err: wax::ParseError) -> Error {
   Error::InvalidInput {
     message: format!("{}", err.expression()),
-    cause: Some(Box::new(err.into_owned()) as Box<dyn std::error::Error>),
+    cause: Some(Box::new(err.into_owned())),
   }
 }
 
 fn rule_error_as_core(err: wax::RuleError) -> Error {
   Error::InvalidInput {
     message: format!("{}", err.expression()),
-    cause: Some(Box::new(err.into_owned()) as Box<dyn std::error::Error>),
+    cause: Some(Box::new(err.into_owned())),
   }
 }
 
-fn resolve_glob(glob: Glob) -> io::Result<Vec<PathBuf>> {
-  let cwd = std::env::current_dir()?;
-  let mut entries = vec![];
-  for maybe_entry in glob.walk(cwd, usize::MAX) {
-    let entry = maybe_entry?;
+/// Lists files within the root recursively, in a stable order, keeping their
+/// path relative to the root. With a glob, only the files it matches are
+/// taken, and the matched directories are taken as a whole. Excluded and
+/// ignored paths are skipped, without descending into directories, and so
+/// are the ignore files themselves and the symlinks looping back.
+fn walk(
+  root: &Path,
+  glob: Option<&Glob>,
+  art: &core::Artifact,
+  from_dir: bool,
+) -> Result<Vec<ResolvedFile>> {
+  let keep = art.keep_symlinks;
+  let exclusions = Exclusions::new(root, &art.exclude)?;
+  let mut walker = WalkBuilder::new(root);
+  walker
+    .standard_filters(false)
+    .parents(true)
+    .git_ignore(art.gitignore)
+    .git_exclude(art.gitignore)
+    .require_git(false)
+    .add_custom_ignore_filename(IGNORE_FILENAME)
+    .follow_links(!keep)
+    .sort_by_file_name(|a, b| a.cmp(b))
+    .filter_entry(move |entry| !exclusions.excludes(entry.path()));
+  let mut files = Vec::new();
+  for maybe_entry in walker.build() {
+    let entry = match maybe_entry {
+      Ok(entry) => entry,
+      Err(err) if is_loop(&err) => {
+        warn!(err = %err, "Skipping a symlink loop");
+        continue;
+      }
+      Err(err) => return Err(Error::Unexpected(Box::new(err))),
+    };
     let path = entry.path();
-    if path.is_file() {
-      entries.push(path.to_path_buf());
+    if entry.depth() == 0
+      || !is_file(path, keep)
+      || is_ignore_file(path, art.gitignore)
+    {
+      continue;
+    }
+    let relative = path.strip_prefix(root).unwrap_or(path).to_path_buf();
+    let matched = match glob {
+      Some(glob) => relative
+        .ancestors()
+        .filter(|p| !p.as_os_str().is_empty())
+        .any(|p| glob.is_match(p)),
+      None => true,
+    };
+    if matched {
+      files.push(ResolvedFile {
+        path: path.to_path_buf(),
+        relative,
+        from_dir,
+      });
+    }
+  }
+  Ok(files)
+}
+
+/// Whether the walk error is a symlink pointing back to one of its ancestors.
+fn is_loop(err: &ignore::Error) -> bool {
+  match err {
+    ignore::Error::Loop { .. } => true,
+    ignore::Error::WithPath { err, .. }
+    | ignore::Error::WithDepth { err, .. }
+    | ignore::Error::WithLineNumber { err, .. } => is_loop(err),
+    ignore::Error::Partial(errs) => errs.iter().all(is_loop),
+    _ => false,
+  }
+}
+
+/// Whether the file lists paths to leave out, rather than being one to take.
+fn is_ignore_file(path: &Path, gitignore: bool) -> bool {
+  match path.file_name().and_then(|n| n.to_str()) {
+    Some(IGNORE_FILENAME) => true,
+    Some(".gitignore") => gitignore,
+    _ => false,
+  }
+}
+
+/// Glob patterns of paths to leave out, matched relative to the walked root.
+struct Exclusions {
+  root: PathBuf,
+  globs: Vec<Glob<'static>>,
+}
+
+impl Exclusions {
+  fn new(root: &Path, patterns: &[String]) -> Result<Exclusions> {
+    let globs = patterns
+      .iter()
+      .map(|pattern| {
+        Glob::new(pattern)
+          .map(Glob::into_owned)
+          .map_err(glob_error_as_core)
+      })
+      .collect::<Result<Vec<_>>>()?;
+    Ok(Exclusions {
+      root: root.to_path_buf(),
+      globs,
+    })
+  }
+
+  fn excludes(&self, path: &Path) -> bool {
+    let relative = path.strip_prefix(&self.root).unwrap_or(path);
+    !relative.as_os_str().is_empty()
+      && self.globs.iter().any(|glob| glob.is_match(relative))
+  }
+}
+
+/// Whether the path is taken as a file: a regular one, or any symlink, when
+/// symlinks are kept.
+fn is_file(path: &Path, keep_symlinks: bool) -> bool {
+  if keep_symlinks && is_symlink(path) {
+    return true;
+  }
+  path.is_file()
+}
+
+/// Whether the path is a directory to descend into. Symlinks to directories
+/// are followed, unless symlinks are kept.
+fn is_dir(path: &Path, keep_symlinks: bool) -> bool {
+  path.is_dir() && !(keep_symlinks && is_symlink(path))
+}
+
+fn is_symlink(path: &Path) -> bool {
+  fs::symlink_metadata(path)
+    .map(|md| md.file_type().is_symlink())
+    .unwrap_or(false)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+  use std::fs;
+  use std::path::{Path, PathBuf};
+
+  use ocilot_core as core;
+  use ocilot_core::error::Result;
+  use ocilot_core::fs::ArtifactResolver as _;
+
+  use crate::glob::ArtifactResolver;
+
+  /// Makes a source tree at the temp dir of the name, with a symlink loop
+  /// within its target directory.
+  fn tree(name: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(name);
+    let _ = fs::remove_dir_all(&root);
+    for dir in ["src", "target/debug", "vendor/acme"] {
+      fs::create_dir_all(root.join(dir)).unwrap();
     }
+    for file in ["src/main.rs", "target/debug/acme", "vendor/acme/lib.rs"] {
+      fs::write(root.join(file), file).unwrap();
+    }
+    std::os::unix::fs::symlink("..", root.join("target/loop")).unwrap();
+    root
+  }
+
+  fn resolve(
+    root: &Path,
+    exclude: &[&str],
+    gitignore: bool,
+  ) -> Result<Vec<String>> {
+    let artifact = core::Artifact {
+      arch: None,
+      from: root.to_string_lossy().to_string(),
+      to: None,
+      mode: None,
+      owner: None,
+      keep_symlinks: false,
+      exclude: exclude.iter().map(|e| e.to_string()).collect(),
+      gitignore,
+      layer: None,
+    };
+    let files = ArtifactResolver {}.resolve(&artifact)?;
+    Ok(
+      files
+        .into_iter()
+        .map(|f| f.relative.to_string_lossy().to_string())
+        .collect(),
+    )
+  }
+
+  #[test]
+  fn exclude() {
+    let root = tree("ocilot-glob-exclude");
+
+    let got = resolve(&root, &["{target,vendor}"], false).unwrap();
+
+    assert_eq!(got, vec!["src/main.rs"]);
+  }
+
+  #[test]
+  fn symlink_loop() {
+    let root = tree("ocilot-glob-loop");
+
+    let got = resolve(&root, &["vendor"], false).unwrap();
+
+    assert_eq!(got, vec!["src/main.rs", "target/debug/acme"]);
+  }
+
+  #[test]
+  fn ocilotignore() {
+    let root = tree("ocilot-glob-ocilotignore");
+    fs::write(root.join(".ocilotignore"), "target/\n*.rs\n!main.rs\n").unwrap();
+
+    let got = resolve(&root, &[], false).unwrap();
+
+    assert_eq!(got, vec!["src/main.rs"]);
+  }
+
+  #[test]
+  fn gitignore() {
+    let root = tree("ocilot-glob-gitignore");
+    fs::write(root.join(".gitignore"), "target/\nvendor/\n").unwrap();
+
+    let got = resolve(&root, &[], true).unwrap();
+
+    assert_eq!(got, vec!["src/main.rs"]);
+    let all = resolve(&root, &[], false).unwrap();
+    assert_eq!(all.first().map(String::as_str), Some(".gitignore"));
+    assert!(all.contains(&"vendor/acme/lib.rs".to_string()));
   }
-  Ok(entries)
 }
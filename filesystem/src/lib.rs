@@ -1,2 +1,3 @@
+mod auditable;
 pub mod file;
 pub mod glob;
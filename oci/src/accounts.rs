@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+use std::io::Read;
+
+use flate2::read::GzDecoder;
+use ocilot_core::error::{Error, Result};
+use ocilot_core::{Id, Owner};
+
+use crate::blob::Blob;
+
+/// Users and groups of an image, as read from its `/etc/passwd` and
+/// `/etc/group` files.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Accounts {
+  /// User names, with their ids and primary group ids.
+  users: HashMap<String, (u32, u32)>,
+  groups: HashMap<String, u32>,
+}
+
+impl Accounts {
+  /// Reads the accounts from the image layers, the topmost layer first.
+  pub(crate) fn read(layers: &[Blob]) -> Result<Accounts> {
+    let mut passwd = None;
+    let mut group = None;
+    for layer in layers.iter().rev() {
+      if passwd.is_some() && group.is_some() {
+        break;
+      }
+      let mut archive = tar::Archive::new(GzDecoder::new(layer.open()?));
+      for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.to_string_lossy().to_string();
+        let slot = match path.trim_start_matches("./") {
+          "etc/passwd" => &mut passwd,
+          "etc/group" => &mut group,
+          _ => continue,
+        };
+        if slot.is_none() {
+          let mut content = String::new();
+          entry.read_to_string(&mut content)?;
+          *slot = Some(content);
+        }
+      }
+    }
+    Ok(Accounts {
+      users: parse_passwd(&passwd.unwrap_or_default()),
+      groups: parse_group(&group.unwrap_or_default()),
+    })
+  }
+
+  /// Resolves the owner into numeric user and group ids. Like in Docker's
+  /// `COPY --chown`, a missing group is the one matching the user.
+  pub(crate) fn resolve(&self, owner: &Option<Owner>) -> Result<(u32, u32)> {
+    let owner = match owner {
+      None => return Ok((0, 0)),
+      Some(owner) => owner,
+    };
+    let (uid, primary_gid) = match &owner.user {
+      Id::Numeric(uid) => (*uid, *uid),
+      Id::Name(name) => *self.users.get(name).ok_or_else(|| {
+        Error::invalid_input(
+          format!("no such user in base image: {}", name).as_str(),
+        )
+      })?,
+    };
+    let gid = match &owner.group {
+      None => primary_gid,
+      Some(Id::Numeric(gid)) => *gid,
+      Some(Id::Name(name)) => *self.groups.get(name).ok_or_else(|| {
+        Error::invalid_input(
+          format!("no such group in base image: {}", name).as_str(),
+        )
+      })?,
+    };
+    Ok((uid, gid))
+  }
+}
+
+/// Tells if resolving the owner requires the accounts of the base image.
+pub(crate) fn has_names(owner: &Option<Owner>) -> bool {
+  match owner {
+    None => false,
+    Some(owner) => {
+      matches!(owner.user, Id::Name(_))
+        || matches!(owner.group, Some(Id::Name(_)))
+    }
+  }
+}
+
+fn parse_passwd(content: &str) -> HashMap<String, (u32, u32)> {
+  content
+    .lines()
+    .filter_map(|line| {
+      let fields: Vec<&str> = line.split(':').collect();
+      if fields.len() < 4 {
+        return None;
+      }
+      let uid = fields[2].parse().ok()?;
+      let gid = fields[3].parse().ok()?;
+      Some((fields[0].to_string(), (uid, gid)))
+    })
+    .collect()
+}
+
+fn parse_group(content: &str) -> HashMap<String, u32> {
+  content
+    .lines()
+    .filter_map(|line| {
+      let fields: Vec<&str> = line.split(':').collect();
+      if fields.len() < 3 {
+        return None;
+      }
+      let gid = fields[2].parse().ok()?;
+      Some((fields[0].to_string(), gid))
+    })
+    .collect()
+}
@@ -0,0 +1,309 @@
+use std::fs::File;
+use std::io::{BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::{Context, Poll};
+use std::time::SystemTime;
+use std::{fs, io, process};
+
+use oci_distribution::client::Client;
+use oci_distribution::manifest::OciDescriptor;
+use oci_distribution::Reference;
+use ocilot_core::error::{Error, Result};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tracing::debug;
+
+/// A blob of an image, like a layer, stored on disk. It's read only when
+/// opened.
+#[derive(Clone, Debug)]
+pub(crate) struct Blob {
+  pub(crate) descriptor: OciDescriptor,
+  pub(crate) path: PathBuf,
+}
+
+impl Blob {
+  pub(crate) fn open(&self) -> Result<BufReader<File>> {
+    Ok(BufReader::new(File::open(&self.path)?))
+  }
+}
+
+/// Downloads the blob of the image into the directory, named by its bare
+/// digest. The content is digested while it's written, and a blob not
+/// matching its descriptor is thrown away. One stored before is kept.
+pub(crate) async fn download(
+  cli: &Client,
+  imageref: &Reference,
+  descriptor: &OciDescriptor,
+  dir: &Path,
+) -> Result<Blob> {
+  let path = dir.join(crate::bare_digest(descriptor.digest.clone()));
+  if let Some(blob) = downloaded(&path, descriptor).await? {
+    return Ok(blob);
+  }
+  let mut out = Digesting::create(&path).await?;
+  let pulled = cli
+    .pull_blob(imageref, &descriptor.digest, &mut out)
+    .await
+    .map_err(|err| Error::Unexpected(Box::from(err)));
+  out.finish(pulled, path, descriptor, imageref).await
+}
+
+/// The blob stored at the path before, if any.
+async fn downloaded(
+  path: &Path,
+  descriptor: &OciDescriptor,
+) -> Result<Option<Blob>> {
+  if !tokio::fs::try_exists(path).await? {
+    return Ok(None);
+  }
+  touch(path)?;
+  debug!(digest = ?descriptor.digest, "Blob already downloaded");
+  Ok(Some(Blob {
+    descriptor: descriptor.clone(),
+    path: path.to_path_buf(),
+  }))
+}
+
+/// Writes the blob into the directory, named by its bare digest, unless it's
+/// there already.
+pub(crate) fn write(
+  dir: &Path,
+  descriptor: OciDescriptor,
+  data: &[u8],
+) -> Result<Blob> {
+  let path = dir.join(crate::bare_digest(descriptor.digest.clone()));
+  if !path.exists() {
+    fs::create_dir_all(dir)?;
+    let partial = partial(&path);
+    let mut file = File::create(&partial)?;
+    file.write_all(data)?;
+    file.flush()?;
+    fs::rename(&partial, &path)?;
+  } else {
+    touch(&path)?;
+  }
+  Ok(Blob { descriptor, path })
+}
+
+/// Marks the stored blob as used now, so the garbage collection keeps it
+/// until its image is indexed, even if no image refers to it yet.
+fn touch(path: &Path) -> io::Result<()> {
+  File::options()
+    .append(true)
+    .open(path)?
+    .set_modified(SystemTime::now())
+}
+
+/// A path to write the blob to, before it's moved into place, once complete.
+/// It's unique, so the same blob may be written by many at once.
+fn partial(path: &Path) -> PathBuf {
+  static NEXT: AtomicUsize = AtomicUsize::new(0);
+  let unique = NEXT.fetch_add(1, Ordering::Relaxed);
+  path.with_extension(format!("{}-{}.partial", process::id(), unique))
+}
+
+/// Writes into a partial file, while digesting what's written.
+struct Digesting {
+  file: tokio::fs::File,
+  partial: PathBuf,
+  hasher: Sha256,
+  size: u64,
+}
+
+impl Digesting {
+  /// Starts writing a blob, to be moved to the path once complete.
+  async fn create(path: &Path) -> Result<Digesting> {
+    let partial = partial(path);
+    Ok(Digesting {
+      file: tokio::fs::File::create(&partial).await?,
+      partial,
+      hasher: Sha256::new(),
+      size: 0,
+    })
+  }
+
+  /// Moves the blob to the path, once pulled whole and matching the
+  /// descriptor. It's thrown away otherwise.
+  async fn finish(
+    mut self,
+    pulled: Result<()>,
+    path: PathBuf,
+    descriptor: &OciDescriptor,
+    imageref: &Reference,
+  ) -> Result<Blob> {
+    let checked = match pulled {
+      Ok(()) => self.file.flush().await.map_err(Error::from).and_then(|_| {
+        self.check(descriptor).map_err(|reason| {
+          Error::invalid_input(&format!(
+            "blob {} of image {} {}",
+            descriptor.digest, imageref, reason
+          ))
+        })
+      }),
+      Err(err) => Err(err),
+    };
+    if let Err(err) = checked {
+      let _ = tokio::fs::remove_file(&self.partial).await;
+      return Err(err);
+    }
+    tokio::fs::rename(&self.partial, &path).await?;
+    debug!(digest = ?descriptor.digest, size = self.size, "Blob downloaded");
+    Ok(Blob {
+      descriptor: descriptor.clone(),
+      path,
+    })
+  }
+
+  /// Checks the content written matches the descriptor, telling why not.
+  fn check(
+    &self,
+    descriptor: &OciDescriptor,
+  ) -> std::result::Result<(), String> {
+    let digest = format!("sha256:{:x}", self.hasher.clone().finalize());
+    if digest != descriptor.digest {
+      return Err(format!("doesn't match its digest, got {}", digest));
+    }
+    if self.size != descriptor.size as u64 {
+      return Err(format!(
+        "has {} bytes, instead of {}",
+        self.size, descriptor.size
+      ));
+    }
+    Ok(())
+  }
+}
+
+impl AsyncWrite for Digesting {
+  fn poll_write(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+    buf: &[u8],
+  ) -> Poll<io::Result<usize>> {
+    let this = &mut *self;
+    let written = Pin::new(&mut this.file).poll_write(cx, buf);
+    if let Poll::Ready(Ok(n)) = written {
+      this.hasher.update(&buf[..n]);
+      this.size += n as u64;
+    }
+    written
+  }
+
+  fn poll_flush(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<io::Result<()>> {
+    Pin::new(&mut self.file).poll_flush(cx)
+  }
+
+  fn poll_shutdown(
+    mut self: Pin<&mut Self>,
+    cx: &mut Context<'_>,
+  ) -> Poll<io::Result<()>> {
+    Pin::new(&mut self.file).poll_shutdown(cx)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::{Path, PathBuf};
+
+  use oci_distribution::manifest::OciDescriptor;
+  use oci_distribution::Reference;
+  use ocilot_core::error::{Error, Result};
+  use tokio::io::AsyncWriteExt;
+
+  use crate::blob::{downloaded, Blob, Digesting};
+
+  const LAYER: &[u8] = b"layer";
+
+  fn descriptor(data: &[u8], size: usize) -> OciDescriptor {
+    OciDescriptor {
+      digest: crate::sha256_digest(data),
+      size: size as i64,
+      ..Default::default()
+    }
+  }
+
+  fn dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+  }
+
+  fn files(dir: &Path) -> Vec<String> {
+    let mut files: Vec<String> = std::fs::read_dir(dir)
+      .unwrap()
+      .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+      .collect();
+    files.sort();
+    files
+  }
+
+  /// Writes the data through a digesting file, as a pull would, and finishes
+  /// it with the pull result.
+  async fn pull(
+    dir: &Path,
+    descriptor: &OciDescriptor,
+    data: &[u8],
+    pulled: Result<()>,
+  ) -> Result<Blob> {
+    let path = dir.join(crate::bare_digest(descriptor.digest.clone()));
+    let imageref = Reference::with_tag(
+      "quay.io".to_string(),
+      "acme/app".to_string(),
+      "v1".to_string(),
+    );
+    let mut out = Digesting::create(&path).await?;
+    out.write_all(data).await?;
+    out.finish(pulled, path, descriptor, &imageref).await
+  }
+
+  #[tokio::test]
+  async fn checked() {
+    let dir = dir("ocilot-blob-checked");
+    let descriptor = descriptor(LAYER, LAYER.len());
+    let path = dir.join(crate::bare_digest(descriptor.digest.clone()));
+
+    assert!(downloaded(&path, &descriptor).await.unwrap().is_none());
+    let blob = pull(&dir, &descriptor, LAYER, Ok(())).await.unwrap();
+
+    assert_eq!(blob.path, path);
+    assert_eq!(std::fs::read(&path).unwrap(), LAYER);
+    assert_eq!(
+      files(&dir),
+      vec![crate::bare_digest(descriptor.digest.clone())]
+    );
+    let again = downloaded(&path, &descriptor).await.unwrap().unwrap();
+    assert_eq!(again.path, path);
+  }
+
+  #[tokio::test]
+  async fn thrown_away() {
+    let dir = dir("ocilot-blob-thrown-away");
+    let other = descriptor(b"other", LAYER.len());
+    let longer = descriptor(LAYER, LAYER.len() + 1);
+    let layer = descriptor(LAYER, LAYER.len());
+    let failed = Err(Error::Unexpected(Box::from("connection reset")));
+
+    let mismatched = pull(&dir, &other, LAYER, Ok(())).await;
+    let truncated = pull(&dir, &longer, LAYER, Ok(())).await;
+    let broken = pull(&dir, &layer, LAYER, failed).await;
+
+    for (res, want) in [
+      (mismatched, "doesn't match its digest"),
+      (truncated, "has 5 bytes, instead of 6"),
+    ] {
+      match res {
+        Err(Error::InvalidInput { message, .. }) => {
+          assert!(message.contains(want), "{}", message)
+        }
+        other => panic!("{:?}", other),
+      }
+    }
+    assert!(matches!(broken, Err(Error::Unexpected(_))), "{:?}", broken);
+    assert!(files(&dir).is_empty(), "{:?}", files(&dir));
+  }
+}
@@ -1,10 +1,16 @@
+use crate::blob::Blob;
+use crate::index::{ImageIndex, IndexEntry};
+use crate::store::{self, Store};
+use crate::{blob, index, provenance, ImageName, OciImage, OciIndex};
+use oci_distribution::manifest::{OciDescriptor, OciImageManifest};
 use ocilot_core::error::{Error, Result};
-use ocilot_core::oci;
 use ocilot_core::oci::{Config, Image};
+use ocilot_core::{oci, Arch};
+use std::fmt::{Display, Formatter};
 use std::fs;
-use std::fs::File;
-use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::SystemTime;
 use tracing::{debug, instrument, warn};
 
 #[derive(Debug)]
@@ -12,53 +18,274 @@ pub struct HomeBased {
   pub config: Box<dyn Config>,
 }
 
+/// Where a cached image comes from.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Origin {
+  /// Pulled from a registry, as a base image.
+  Pulled,
+  /// Built by ocilot.
+  Built,
+}
+
+/// What the cache records of an image, within the index of its store, so
+/// it's listed and found without reading its blobs.
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct Cached {
+  pub image: String,
+  pub tags: Vec<String>,
+  /// The digest of the manifest, or of the index, like "sha256:<hex>".
+  pub digest: String,
+  /// The digest of the config. None, for a multi-platform image.
+  pub config: Option<String>,
+  pub created: Option<SystemTime>,
+  /// The size of the content: the configs and the layers.
+  pub size: u64,
+  pub origin: Origin,
+  pub fingerprint: Option<String>,
+}
+
+impl HomeBased {
+  /// Lists the cached images, as recorded, without loading them. The ones
+  /// migrated from an older cache miss their names, and multi-platform ones
+  /// their size and creation time.
+  pub fn images(&self) -> Result<Vec<Cached>> {
+    let store = Store::open(&self.config.workdir()?)?;
+    Ok(store.images()?.iter().map(store::cached).collect())
+  }
+
+  /// Loads the cached images the records of which are wanted. The ones with
+  /// blobs gone missing are skipped.
+  fn load_where(
+    &self,
+    wanted: impl Fn(&Cached) -> bool,
+  ) -> Result<Vec<Box<dyn Image>>> {
+    let store = Store::open(&self.config.workdir()?)?;
+    let mut images = Vec::new();
+    for entry in store.images()? {
+      let cached = store::cached(&entry);
+      if !wanted(&cached) {
+        continue;
+      }
+      match load(&store, &entry, cached) {
+        Ok(image) => images.push(image),
+        Err(err) => {
+          warn!(digest = ?entry.digest, error = %err, "Cached image unreadable");
+        }
+      }
+    }
+    Ok(images)
+  }
+}
+
 impl oci::Cache for HomeBased {
   #[instrument(ret, level = "trace")]
   fn list(&self) -> Result<Vec<Box<dyn Image>>> {
-    warn!("Not yet implemented");
-    Ok(Vec::new())
+    self.load_where(|_| true)
+  }
+
+  #[instrument(ret, level = "trace")]
+  fn find(
+    &self,
+    image: &str,
+    tag: Option<&str>,
+  ) -> Result<Vec<Box<dyn Image>>> {
+    self.load_where(|cached| {
+      cached.image == image
+        && tag.is_none_or(|t| cached.tags.iter().any(|c| c == t))
+    })
+  }
+
+  #[instrument(ret, level = "trace")]
+  fn get(&self, digest: &str) -> Result<Option<Box<dyn Image>>> {
+    let digest = crate::bare_digest(digest.to_string());
+    let mut found = self.load_where(|cached| {
+      crate::bare_digest(cached.digest.clone()) == digest
+    })?;
+    Ok(found.pop())
   }
 }
 
+impl Display for Origin {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Origin::Pulled => f.pad("pulled"),
+      Origin::Built => f.pad("built"),
+    }
+  }
+}
+
+/// Loads the image of the index entry from the store. Only the platforms
+/// stored, as pulled or built, are loaded of a multi-platform one.
+fn load(
+  store: &Store,
+  entry: &IndexEntry,
+  cached: Cached,
+) -> Result<Box<dyn Image>> {
+  let name = ImageName {
+    image: cached.image,
+    tags: cached.tags,
+  };
+  let raw = fs::read(store.blob_path(&entry.digest))?;
+  if !index::is_index(&raw) {
+    let image =
+      load_image(store, &entry.digest, &raw, name, cached.fingerprint)?;
+    return Ok(Box::new(image) as Box<dyn Image>);
+  }
+  let index: ImageIndex = serde_json::from_slice(&raw)
+    .map_err(|err| Error::Unexpected(Box::from(err)))?;
+  let untagged = ImageName {
+    image: name.image.clone(),
+    tags: vec![],
+  };
+  let mut platforms = Vec::new();
+  for platform in &index.manifests {
+    let arch = match &platform.platform {
+      Some(p) if p.os == "linux" => Arch::from_str(&p.architecture).ok(),
+      _ => None,
+    };
+    let raw = fs::read(store.blob_path(&platform.digest));
+    if let (Some(arch), Ok(raw)) = (arch, raw) {
+      let image =
+        load_image(store, &platform.digest, &raw, untagged.clone(), None)?;
+      platforms.push((arch, image));
+    }
+  }
+  Ok(Box::new(OciIndex {
+    index,
+    digest: entry.digest.clone(),
+    platforms,
+    name,
+    workdir: store.root().to_path_buf(),
+    fingerprint: cached.fingerprint,
+  }) as Box<dyn Image>)
+}
+
+fn load_image(
+  store: &Store,
+  digest: &str,
+  raw: &[u8],
+  name: ImageName,
+  fingerprint: Option<String>,
+) -> Result<OciImage> {
+  let manifest: OciImageManifest = serde_json::from_slice(raw)
+    .map_err(|err| Error::Unexpected(Box::from(err)))?;
+  let config = fs::read(store.blob_path(&manifest.config.digest))?;
+  let layers = manifest
+    .layers
+    .iter()
+    .map(|descriptor| Blob {
+      descriptor: descriptor.clone(),
+      path: store.blob_path(&descriptor.digest),
+    })
+    .collect();
+  Ok(OciImage {
+    manifest,
+    digest: digest.to_string(),
+    config,
+    layers,
+    name,
+    workdir: store.root().to_path_buf(),
+    fingerprint,
+    index: None,
+  })
+}
+
+/// Stores the image, and lists it within the index of the store.
 #[instrument(ret, level = "trace")]
 pub(crate) fn persist_image(
   workdir: PathBuf,
-  image: &crate::OciImage,
+  image: &OciImage,
+  origin: Origin,
 ) -> Result<()> {
-  let digest = image.digest();
-  let (prefix, rest) = digest.split_at(3);
-  let imdir = workdir.join("images").join(prefix).join(rest);
-  fs::create_dir_all(&imdir)?;
-
-  for layer in &image.data.layers {
-    let digest = crate::bare_digest(layer.sha256_digest());
-    let new_layer_path = imdir.join(&digest);
-    let mut new_layer_file = File::create(&new_layer_path)?;
-    new_layer_file.write_all(&(layer.data).as_slice())?;
-    new_layer_file.flush()?;
-    debug!(layer = ?digest, "Layer cached");
-  }
-  let manifest_path = imdir.join("manifest.json");
-  let manifest = image
-    .data
+  let store = Store::open(&workdir)?;
+  let descriptor = store_image(&store, image)?;
+  let cached = Cached {
+    image: image.name.image.clone(),
+    tags: image.name.tags.clone(),
+    digest: descriptor.digest.clone(),
+    config: Some(image.manifest.config.digest.clone()),
+    created: image.created_at(),
+    size: image.size(),
+    origin,
+    fingerprint: image.fingerprint.clone(),
+  };
+  store.add(store::entry(descriptor, &cached))?;
+  debug!(image = ?image.digest, "Image cached");
+  Ok(())
+}
+
+/// Stores the config and the manifest of the image, without listing it, like
+/// the platform images of a multi-platform one. The layers are expected to
+/// be stored already, as they're written. A manifest stored before, as
+/// pulled, is kept.
+pub(crate) fn store_image(
+  store: &Store,
+  image: &OciImage,
+) -> Result<OciDescriptor> {
+  blob::write(&store.blobs(), image.manifest.config.clone(), &image.config)?;
+  let media_type = image
     .manifest
-    .as_ref()
-    .ok_or(Error::Bug("no image manifest".to_string()))?;
-  let manifest_cnt = serde_json::to_string(manifest)
-    .map_err(|err| Error::Unexpected(Box::from(err)))?;
-  let mut manifest_file = File::create(&manifest_path)?;
-  manifest_file.write_all(manifest_cnt.as_bytes())?;
-  manifest_file.flush()?;
-  debug!(manifest = ?manifest_path, "Manifest cached");
-  let config_path =
-    imdir.join(crate::bare_digest(manifest.config.digest.to_string()));
-  let mut config_file = File::create(&config_path)?;
-  config_file.write_all(image.data.config.data.as_slice())?;
-  config_file.flush()?;
-  debug!(config = ?config_path, "Config cached");
-  let mut version_file = File::create(imdir.join("version"))?;
-  version_file.write_all("Directory Transport Version: 1.1\n".as_bytes())?;
-  version_file.flush()?;
-  debug!(image = ?digest, "Image cached");
+    .media_type
+    .clone()
+    .unwrap_or_else(|| provenance::OCI_MANIFEST.to_string());
+  stored_or_put(store, &image.digest, &media_type, || image.manifest_data())
+}
+
+/// Stores the index of a multi-platform image, and lists it within the index
+/// of the store. The platform images are expected to be stored on their own.
+#[instrument(ret, level = "trace")]
+pub(crate) fn persist_index(
+  workdir: PathBuf,
+  index: &OciIndex,
+  origin: Origin,
+) -> Result<()> {
+  let store = Store::open(&workdir)?;
+  let media_type = index
+    .index
+    .media_type
+    .clone()
+    .unwrap_or_else(|| index::OCI_INDEX.to_string());
+  let descriptor = stored_or_put(&store, &index.digest, &media_type, || {
+    serde_json::to_vec(&index.index)
+      .map_err(|err| Error::Unexpected(Box::from(err)))
+  })?;
+  let cached = Cached {
+    image: index.name.image.clone(),
+    tags: index.name.tags.clone(),
+    digest: descriptor.digest.clone(),
+    config: None,
+    created: index.created_at(),
+    size: index.size(),
+    origin,
+    fingerprint: index.fingerprint.clone(),
+  };
+  store.add(store::entry(descriptor, &cached))?;
+  debug!(image = ?index.digest, "Image index cached");
   Ok(())
 }
+
+/// The descriptor of the stored blob of the digest, or of the data, once
+/// stored.
+fn stored_or_put(
+  store: &Store,
+  digest: &str,
+  media_type: &str,
+  data: impl FnOnce() -> Result<Vec<u8>>,
+) -> Result<OciDescriptor> {
+  match fs::metadata(store.blob_path(digest)) {
+    Ok(meta) => Ok(OciDescriptor {
+      media_type: media_type.to_string(),
+      digest: format!("sha256:{}", crate::bare_digest(digest.to_string())),
+      size: meta.len() as i64,
+      urls: None,
+      annotations: None,
+    }),
+    Err(_) => store.put(media_type, &data()?),
+  }
+}
+
+/// The directory of what ocilot adds to the image, like its attestations.
+pub(crate) fn image_dir(workdir: &Path, digest: &str) -> PathBuf {
+  let (prefix, rest) = digest.split_at(3);
+  workdir.join("images").join(prefix).join(rest)
+}
@@ -0,0 +1,379 @@
+use std::collections::HashSet;
+use std::io::{Cursor, Read};
+use std::time::SystemTime;
+
+use oci_distribution::manifest::{OciDescriptor, OciImageManifest};
+use ocilot_core::build;
+use ocilot_core::error::{Error, Result};
+use ocilot_core::oci::{
+  ConfigChanges, Construction, Image, Input, LayerInfo, Settings,
+};
+use ocilot_core::Arch;
+use serde_json::{json, Value};
+use tracing::{debug, instrument};
+
+use crate::accounts::Accounts;
+use crate::cache::Origin;
+use crate::index::{self, ImageIndex, IndexEntry};
+use crate::layer::Layer;
+use crate::store::Store;
+use crate::{
+  accounts, blob, cache, layer, mutation, ImageName, OciImage, OciIndex,
+};
+
+const DOCKER_MANIFEST: &str =
+  "application/vnd.docker.distribution.manifest.v2+json";
+const DOCKER_LAYER: &str = "application/vnd.docker.image.rootfs.diff.tar.gzip";
+const OCI_LAYER: &str = "application/vnd.oci.image.layer.v1.tar+gzip";
+
+#[derive(Debug)]
+pub(crate) struct OciImageConstruction {
+  pub(crate) base: OciImage,
+  pub(crate) layers: Vec<Layer>,
+  pub(crate) created: SystemTime,
+  pub(crate) config: ConfigChanges,
+  /// The platform built for, when known. Inputs of other ones are skipped.
+  pub(crate) arch: Option<Arch>,
+  /// The directories and symbolic links of the base, once read.
+  existing: Option<HashSet<String>>,
+}
+
+impl OciImageConstruction {
+  /// Constructs on top of a single-platform image, for the architecture its
+  /// config declares, or else the one requested.
+  pub(crate) fn new(
+    base: &OciImage,
+    settings: &Settings,
+  ) -> OciImageConstruction {
+    let declared = base.platforms().ok().and_then(|p| p.into_iter().next());
+    let requested = match settings.archs.len() {
+      1 => settings.archs.iter().next().cloned(),
+      _ => None,
+    };
+    Self::for_platform(base, declared.or(requested), settings)
+  }
+
+  pub(crate) fn for_platform(
+    base: &OciImage,
+    arch: Option<Arch>,
+    settings: &Settings,
+  ) -> OciImageConstruction {
+    OciImageConstruction {
+      base: base.clone(),
+      layers: Vec::new(),
+      created: settings.created.unwrap_or_else(|| settings.clock.now()),
+      config: settings.config.clone(),
+      arch,
+      existing: None,
+    }
+  }
+}
+
+impl Construction for OciImageConstruction {
+  #[instrument(skip(files), level = "trace")]
+  fn add(&mut self, files: Vec<Input>) -> Result<Vec<LayerInfo>> {
+    let files: Vec<Input> = files
+      .into_iter()
+      .filter(|input| match (&input.arch, &self.arch) {
+        (Some(want), Some(have)) => want == have,
+        _ => true,
+      })
+      .collect();
+    if files.is_empty() {
+      debug!(arch = ?self.arch, "Nothing to add for architecture");
+      return Ok(vec![]);
+    }
+    let accounts = if files.iter().any(|f| accounts::has_names(&f.owner)) {
+      Accounts::read(&self.base.layers)?
+    } else {
+      Accounts::default()
+    };
+    if self.existing.is_none() {
+      self.existing = Some(layer::paths(&self.base.layers)?);
+    }
+    let existing = self.existing.as_ref().expect("base paths are read");
+    let layer = layer::pack(files, self.created, &accounts, existing)?;
+    debug!(layer = ?layer, "Layer packed");
+    let info = LayerInfo {
+      arch: None,
+      digest: crate::bare_digest(layer.digest.clone()),
+      size: layer.data.len() as u64,
+    };
+    self.layers.push(layer);
+    Ok(vec![info])
+  }
+
+  #[instrument(ret, level = "trace")]
+  fn build(
+    &self,
+    name: &build::ImageName,
+    fingerprint: &str,
+  ) -> Result<Box<dyn Image>> {
+    let image = self.build_image(name, Some(fingerprint))?;
+    cache::persist_image(image.workdir.clone(), &image, Origin::Built)?;
+    Ok(Box::new(image) as Box<dyn Image>)
+  }
+}
+
+impl OciImageConstruction {
+  fn build_image(
+    &self,
+    name: &build::ImageName,
+    fingerprint: Option<&str>,
+  ) -> Result<OciImage> {
+    let mut manifest = self.base.manifest.clone();
+    let mut config = self.base.config()?;
+    let created = chrono::DateTime::<chrono::Utc>::from(self.created)
+      .to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+    let media_type = layer_media_type(&manifest);
+    let mut added = Vec::new();
+    for layer in &self.layers {
+      config["rootfs"]["diff_ids"]
+        .as_array_mut()
+        .ok_or_else(|| {
+          Error::invalid_input("base image config has no rootfs diff_ids")
+        })?
+        .push(Value::from(layer.diff_id.clone()));
+      append_history(&mut config, &created)?;
+      let descriptor = OciDescriptor {
+        media_type: media_type.to_string(),
+        digest: layer.digest.clone(),
+        size: layer.data.len() as i64,
+        urls: None,
+        annotations: None,
+      };
+      manifest.layers.push(descriptor.clone());
+      added.push((descriptor, layer));
+    }
+    mutation::apply(&self.config, &mut config)?;
+    config["created"] = Value::from(created);
+
+    let config_data = serde_json::to_vec(&config)
+      .map_err(|err| Error::Unexpected(Box::from(err)))?;
+    manifest.config.digest = crate::sha256_digest(&config_data);
+    manifest.config.size = config_data.len() as i64;
+    let manifest_data = serde_json::to_vec(&manifest)
+      .map_err(|err| Error::Unexpected(Box::from(err)))?;
+    let digest = crate::sha256_digest(&manifest_data);
+
+    // only the new layers are written, the base ones are stored already
+    let workdir = self.base.workdir.clone();
+    let blobs = Store::open(&workdir)?.blobs();
+    let mut layers = self.base.layers.clone();
+    for (descriptor, layer) in added {
+      layers.push(blob::write(&blobs, descriptor, &layer.data)?);
+    }
+    let image = OciImage {
+      manifest,
+      digest,
+      config: config_data,
+      layers,
+      name: ImageName {
+        image: name.image.to_string(),
+        tags: name.tags.iter().map(|t| t.to_string()).collect(),
+      },
+      workdir,
+      fingerprint: fingerprint.map(|f| f.to_string()),
+      index: None,
+    };
+    Ok(image)
+  }
+}
+
+/// Constructs a multi-platform image, by stacking artifacts on top of each
+/// of the platform images of the base index.
+#[derive(Debug)]
+pub(crate) struct IndexConstruction {
+  pub(crate) base: OciIndex,
+  pub(crate) platforms: Vec<(Arch, OciImageConstruction)>,
+}
+
+impl IndexConstruction {
+  pub(crate) fn new(base: &OciIndex, settings: &Settings) -> Self {
+    let archs = &settings.archs;
+    let platforms = base
+      .platforms
+      .iter()
+      .filter(|(arch, _)| archs.is_empty() || archs.contains(arch))
+      .map(|(arch, im)| {
+        let constr =
+          OciImageConstruction::for_platform(im, Some(arch.clone()), settings);
+        (arch.clone(), constr)
+      })
+      .collect();
+    IndexConstruction {
+      base: base.clone(),
+      platforms,
+    }
+  }
+}
+
+impl Construction for IndexConstruction {
+  #[instrument(skip(files), level = "trace")]
+  fn add(&mut self, files: Vec<Input>) -> Result<Vec<LayerInfo>> {
+    // inputs are read once, but common ones are needed by every platform
+    let mut buffered = Vec::new();
+    for mut input in files {
+      let mut data = Vec::new();
+      input.from.read_to_end(&mut data)?;
+      buffered.push((input, data));
+    }
+    let mut written = Vec::new();
+    for (arch, constr) in &mut self.platforms {
+      let inputs = buffered
+        .iter()
+        .filter(|(i, _)| i.arch.is_none() || i.arch.as_ref() == Some(arch))
+        .map(|(i, data)| Input {
+          arch: i.arch.clone(),
+          from: Box::new(Cursor::new(data.clone())),
+          to: i.to.clone(),
+          mode: i.mode,
+          owner: i.owner.clone(),
+          link: i.link.clone(),
+        })
+        .collect();
+      for layer in constr.add(inputs)? {
+        written.push(LayerInfo {
+          arch: Some(arch.clone()),
+          ..layer
+        });
+      }
+    }
+    Ok(written)
+  }
+
+  #[instrument(ret, level = "trace")]
+  fn build(
+    &self,
+    name: &build::ImageName,
+    fingerprint: &str,
+  ) -> Result<Box<dyn Image>> {
+    let untagged = build::ImageName {
+      image: name.image.clone(),
+      tags: HashSet::new(),
+    };
+    let store = Store::open(&self.base.workdir)?;
+    let mut manifests = Vec::new();
+    let mut platforms = Vec::new();
+    for (arch, constr) in &self.platforms {
+      let image = constr.build_image(&untagged, None)?;
+      cache::store_image(&store, &image)?;
+      let manifest_data = image.manifest_data()?;
+      let base_entry = self.base.index.find(arch).ok_or_else(|| {
+        Error::Bug(format!("no base platform for arch: {}", arch))
+      })?;
+      manifests.push(IndexEntry {
+        media_type: base_entry.media_type.clone(),
+        digest: crate::sha256_digest(&manifest_data),
+        size: manifest_data.len() as i64,
+        platform: base_entry.platform.clone(),
+        annotations: None,
+      });
+      platforms.push((arch.clone(), image));
+    }
+    // a fresh index, as a Docker manifest list would stay one otherwise
+    let index = ImageIndex {
+      schema_version: 2,
+      media_type: Some(index::OCI_INDEX.to_string()),
+      manifests,
+      annotations: None,
+    };
+    let index_data = serde_json::to_vec(&index)
+      .map_err(|err| Error::Unexpected(Box::from(err)))?;
+    let built = OciIndex {
+      index,
+      digest: crate::sha256_digest(&index_data),
+      platforms,
+      name: ImageName {
+        image: name.image.to_string(),
+        tags: name.tags.iter().map(|t| t.to_string()).collect(),
+      },
+      workdir: self.base.workdir.clone(),
+      fingerprint: Some(fingerprint.to_string()),
+    };
+    cache::persist_index(built.workdir.clone(), &built, Origin::Built)?;
+    Ok(Box::new(built) as Box<dyn Image>)
+  }
+}
+
+fn append_history(config: &mut Value, created: &str) -> Result<()> {
+  if config["history"].is_null() {
+    config["history"] = json!([]);
+  }
+  config["history"]
+    .as_array_mut()
+    .ok_or_else(|| Error::invalid_input("base image history isn't a list"))?
+    .push(json!({
+      "created": created,
+      "created_by": format!("ocilot {}", env!("CARGO_PKG_VERSION")),
+      "comment": "artifacts added by ocilot",
+    }));
+  Ok(())
+}
+
+/// Picks a layer media type from the same family as the base manifest.
+fn layer_media_type(manifest: &OciImageManifest) -> &'static str {
+  match manifest.media_type.as_deref() {
+    Some(DOCKER_MANIFEST) => DOCKER_LAYER,
+    _ => OCI_LAYER,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::HashSet;
+  use std::io::Cursor;
+  use std::path::PathBuf;
+
+  use oci_distribution::manifest::OciImageManifest;
+  use ocilot_core::oci::{Clock, ConfigChanges, Construction, Input, Settings};
+  use ocilot_core::Arch;
+
+  use crate::construction::OciImageConstruction;
+  use crate::{ImageName, OciImage};
+
+  fn input(arch: Option<Arch>, to: &str) -> Input {
+    Input {
+      arch,
+      from: Box::new(Cursor::new(b"x".to_vec())),
+      to: to.to_string(),
+      mode: 0o644,
+      owner: None,
+      link: None,
+    }
+  }
+
+  #[test]
+  fn requested_arch() {
+    // the config of the base doesn't declare its architecture
+    let base = OciImage {
+      manifest: OciImageManifest::default(),
+      digest: "sha256:base".to_string(),
+      config: br#"{"rootfs":{"diff_ids":[]}}"#.to_vec(),
+      layers: vec![],
+      name: ImageName {
+        image: "quay.io/acme/base".to_string(),
+        tags: vec![],
+      },
+      workdir: PathBuf::new(),
+      fingerprint: None,
+      index: None,
+    };
+    let settings = Settings {
+      archs: HashSet::from([Arch::Arm64]),
+      created: None,
+      config: ConfigChanges::default(),
+      clock: Clock::default(),
+    };
+    let mut constr = OciImageConstruction::new(&base, &settings);
+
+    let skipped = constr.add(vec![input(Some(Arch::Amd64), "/bin/app")]);
+    let added = constr.add(vec![
+      input(Some(Arch::Arm64), "/bin/app"),
+      input(None, "/etc/app.conf"),
+    ]);
+
+    assert!(skipped.unwrap().is_empty());
+    assert_eq!(added.unwrap().len(), 1);
+  }
+}
@@ -0,0 +1,86 @@
+use std::collections::{BTreeMap, HashSet};
+use std::str::FromStr;
+
+use ocilot_core::Arch;
+use serde::{Deserialize, Serialize};
+
+pub(crate) const OCI_INDEX: &str = "application/vnd.oci.image.index.v1+json";
+pub(crate) const DOCKER_MANIFEST_LIST: &str =
+  "application/vnd.docker.distribution.manifest.list.v2+json";
+
+/// An image index (or a Docker manifest list), pointing to per-platform
+/// image manifests.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ImageIndex {
+  pub(crate) schema_version: u8,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) media_type: Option<String>,
+  pub(crate) manifests: Vec<IndexEntry>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) annotations: Option<BTreeMap<String, String>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct IndexEntry {
+  pub(crate) media_type: String,
+  pub(crate) digest: String,
+  pub(crate) size: i64,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) platform: Option<Platform>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) annotations: Option<BTreeMap<String, String>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Platform {
+  pub(crate) architecture: String,
+  pub(crate) os: String,
+  #[serde(rename = "os.version", skip_serializing_if = "Option::is_none")]
+  pub(crate) os_version: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub(crate) variant: Option<String>,
+}
+
+impl ImageIndex {
+  /// Finds a Linux manifest for the given architecture.
+  pub(crate) fn find(&self, arch: &Arch) -> Option<&IndexEntry> {
+    let arch = arch.to_string();
+    self.manifests.iter().find(|entry| match &entry.platform {
+      Some(p) => p.os == "linux" && p.architecture == arch,
+      None => false,
+    })
+  }
+}
+
+impl ImageIndex {
+  /// Lists the Linux architectures of the index, skipping the unknown ones.
+  pub(crate) fn archs(&self) -> HashSet<Arch> {
+    self
+      .manifests
+      .iter()
+      .filter_map(|entry| entry.platform.as_ref())
+      .filter(|p| p.os == "linux")
+      .filter_map(|p| Arch::from_str(&p.architecture).ok())
+      .collect()
+  }
+}
+
+/// Tells if the raw manifest is an image index, rather than an image manifest.
+pub(crate) fn is_index(raw: &[u8]) -> bool {
+  #[derive(Deserialize)]
+  #[serde(rename_all = "camelCase")]
+  struct Probe {
+    media_type: Option<String>,
+    manifests: Option<serde_json::Value>,
+  }
+  match serde_json::from_slice::<Probe>(raw) {
+    Ok(probe) => match probe.media_type.as_deref() {
+      Some(OCI_INDEX) | Some(DOCKER_MANIFEST_LIST) => true,
+      Some(_) => false,
+      None => probe.manifests.is_some(),
+    },
+    Err(_) => false,
+  }
+}
@@ -0,0 +1,256 @@
+use std::collections::{BTreeSet, HashSet};
+use std::io;
+use std::io::{Read, Write};
+use std::time::SystemTime;
+
+use flate2::read::GzDecoder;
+use flate2::{Compression, GzBuilder};
+use ocilot_core::error::Result;
+use ocilot_core::oci::{Input, Link};
+use tar::{EntryType, Header};
+
+use crate::accounts::Accounts;
+use crate::blob::Blob;
+
+/// The gzip header value for an unknown operating system.
+const UNKNOWN_OS: u8 = 255;
+
+/// A gzip compressed tarball, ready to be stacked on top of an image.
+#[derive(Clone)]
+pub(crate) struct Layer {
+  pub(crate) data: Vec<u8>,
+  /// A digest of the uncompressed tarball, as used in image config.
+  pub(crate) diff_id: String,
+  /// A digest of the compressed tarball, as used in image manifest.
+  pub(crate) digest: String,
+}
+
+impl std::fmt::Debug for Layer {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Layer")
+      .field("digest", &self.digest)
+      .field("diff_id", &self.diff_id)
+      .field("size", &self.data.len())
+      .finish()
+  }
+}
+
+/// Packs the given inputs into a new layer, creating the parent directories
+/// missing from the existing paths along the way, owned by root. The existing
+/// ones are left as they are. Hard links need to follow their targets, in the
+/// order of destinations.
+///
+/// The layer is reproducible: entries are sorted, stamped with the given
+/// time, and compressed with a stable gzip header.
+pub(crate) fn pack(
+  mut inputs: Vec<Input>,
+  created: SystemTime,
+  accounts: &Accounts,
+  existing: &HashSet<String>,
+) -> Result<Layer> {
+  let mtime = created
+    .duration_since(SystemTime::UNIX_EPOCH)
+    .map(|d| d.as_secs())
+    .unwrap_or_default();
+  inputs.sort_by(|a, b| a.to.cmp(&b.to));
+  let mut tarball = tar::Builder::new(Vec::new());
+  let mut dirs = BTreeSet::new();
+  for mut input in inputs {
+    let path = input.to.trim_start_matches('/').to_string();
+    for dir in parents(&path) {
+      if !existing.contains(&dir) && dirs.insert(dir.clone()) {
+        let mut header = header(EntryType::Directory, 0o755, mtime);
+        tarball.append_data(&mut header, format!("{}/", dir), io::empty())?;
+      }
+    }
+    let (uid, gid) = accounts.resolve(&input.owner)?;
+    let kind = match &input.link {
+      None => EntryType::Regular,
+      Some(Link::Symbolic(_)) => EntryType::Symlink,
+      Some(Link::Hard(_)) => EntryType::Link,
+    };
+    let mut header = header(kind, input.mode, mtime);
+    header.set_uid(uid as u64);
+    header.set_gid(gid as u64);
+    match &input.link {
+      None => {
+        let mut data = Vec::new();
+        input.from.read_to_end(&mut data)?;
+        header.set_size(data.len() as u64);
+        tarball.append_data(&mut header, &path, data.as_slice())?;
+      }
+      Some(Link::Symbolic(target)) => {
+        tarball.append_link(&mut header, &path, target)?;
+      }
+      Some(Link::Hard(target)) => {
+        let target = target.trim_start_matches('/');
+        tarball.append_link(&mut header, &path, target)?;
+      }
+    }
+  }
+  let tar = tarball.into_inner()?;
+  let diff_id = crate::sha256_digest(&tar);
+  let data = gzip(&tar)?;
+  let digest = crate::sha256_digest(&data);
+  Ok(Layer {
+    data,
+    diff_id,
+    digest,
+  })
+}
+
+/// Compresses the tarball, with a stable gzip header.
+fn gzip(tar: &[u8]) -> io::Result<Vec<u8>> {
+  let mut gz = GzBuilder::new()
+    .mtime(0)
+    .operating_system(UNKNOWN_OS)
+    .write(Vec::new(), Compression::default());
+  gz.write_all(tar)?;
+  gz.finish()
+}
+
+fn header(kind: EntryType, mode: u32, mtime: u64) -> Header {
+  let mut header = Header::new_gnu();
+  header.set_entry_type(kind);
+  header.set_mode(mode);
+  header.set_uid(0);
+  header.set_gid(0);
+  header.set_mtime(mtime);
+  header.set_size(0);
+  if let Some(gnu) = header.as_gnu_mut() {
+    gnu.set_atime(0);
+    gnu.set_ctime(0);
+  }
+  header
+}
+
+/// Lists the directories and symbolic links of the image layers, from the
+/// bottom one, as relative paths. The ones deleted by an upper layer are
+/// dropped.
+pub(crate) fn paths(layers: &[Blob]) -> Result<HashSet<String>> {
+  let mut paths = HashSet::new();
+  for layer in layers {
+    let mut archive = tar::Archive::new(GzDecoder::new(layer.open()?));
+    for entry in archive.entries()? {
+      let entry = entry?;
+      let path = entry.path()?.to_string_lossy().to_string();
+      let path = path.trim_start_matches("./").trim_end_matches('/');
+      let (dir, name) = match path.rsplit_once('/') {
+        Some((dir, name)) => (format!("{}/", dir), name),
+        None => (String::new(), path),
+      };
+      if let Some(deleted) = name.strip_prefix(".wh.") {
+        paths.remove(&format!("{}{}", dir, deleted));
+        continue;
+      }
+      match entry.header().entry_type() {
+        EntryType::Directory | EntryType::Symlink => {
+          paths.insert(path.to_string());
+        }
+        _ => {}
+      }
+    }
+  }
+  Ok(paths)
+}
+
+/// Lists all parent directories of a relative path, outermost first.
+fn parents(path: &str) -> Vec<String> {
+  let parts: Vec<&str> = path.split('/').filter(|p| !p.is_empty()).collect();
+  (1..parts.len()).map(|i| parts[..i].join("/")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::{self, Cursor, Read};
+  use std::time::SystemTime;
+
+  use flate2::read::GzDecoder;
+  use ocilot_core::oci::Input;
+  use tar::EntryType;
+
+  use crate::accounts::Accounts;
+  use crate::blob::{self, Blob};
+  use crate::layer::{self, header};
+
+  /// Writes a base layer of the directories, by their paths, modes and
+  /// owners.
+  fn base(dirs: &[(&str, u32, u64)]) -> Blob {
+    let mut tarball = tar::Builder::new(Vec::new());
+    for (path, mode, owner) in dirs {
+      let mut header = header(EntryType::Directory, *mode, 0);
+      header.set_uid(*owner);
+      header.set_gid(*owner);
+      tarball.append_data(&mut header, path, io::empty()).unwrap();
+    }
+    let tar = tarball.into_inner().unwrap();
+    let data = layer::gzip(&tar).unwrap();
+    let dir = std::env::temp_dir().join("ocilot-layer-base");
+    let descriptor = oci_distribution::manifest::OciDescriptor {
+      digest: crate::sha256_digest(&data),
+      size: data.len() as i64,
+      ..Default::default()
+    };
+    blob::write(&dir, descriptor, &data).unwrap()
+  }
+
+  fn input(to: &str) -> Input {
+    Input {
+      arch: None,
+      from: Box::new(Cursor::new(b"x".to_vec())),
+      to: to.to_string(),
+      mode: 0o644,
+      owner: None,
+      link: None,
+    }
+  }
+
+  #[test]
+  fn parents() {
+    let base = base(&[
+      ("tmp/", 0o1777, 0),
+      ("home/", 0o755, 0),
+      ("home/nonroot/", 0o700, 65532),
+    ]);
+    let existing = layer::paths(&[base]).unwrap();
+    let inputs = vec![
+      input("/tmp/x"),
+      input("/home/nonroot/app/y"),
+      input("/opt/acme/z"),
+    ];
+
+    let packed = layer::pack(
+      inputs,
+      SystemTime::UNIX_EPOCH,
+      &Accounts::default(),
+      &existing,
+    )
+    .unwrap();
+
+    let mut tar = Vec::new();
+    GzDecoder::new(packed.data.as_slice())
+      .read_to_end(&mut tar)
+      .unwrap();
+    let mut archive = tar::Archive::new(tar.as_slice());
+    let entries: Vec<(String, u32)> = archive
+      .entries()
+      .unwrap()
+      .map(|e| {
+        let e = e.unwrap();
+        let path = e.path().unwrap().to_string_lossy().to_string();
+        (path, e.header().mode().unwrap())
+      })
+      .collect();
+    assert_eq!(
+      entries,
+      vec![
+        ("home/nonroot/app/".to_string(), 0o755),
+        ("home/nonroot/app/y".to_string(), 0o644),
+        ("opt/".to_string(), 0o755),
+        ("opt/acme/".to_string(), 0o755),
+        ("opt/acme/z".to_string(), 0o644),
+        ("tmp/x".to_string(), 0o644),
+      ]
+    );
+  }
+}
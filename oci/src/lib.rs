@@ -1,8 +1,26 @@
-use oci_distribution as oci;
+use oci_distribution::manifest::OciImageManifest;
+use ocilot_core::error::{Error, Result};
+use ocilot_core::Arch;
+use sha2::{Digest, Sha256};
 use std::fmt::Formatter;
+use std::path::PathBuf;
+use std::time::SystemTime;
+mod accounts;
+mod blob;
 pub mod cache;
 pub mod config;
+mod construction;
+mod index;
+mod layer;
+mod mutation;
+mod provenance;
 pub mod registry;
+mod sbom;
+pub mod signing;
+mod store;
+pub mod transfer;
+
+pub use store::Collected;
 
 #[derive(PartialEq, Eq, Debug, Clone)]
 pub(crate) struct ImageName {
@@ -10,24 +28,107 @@ pub(crate) struct ImageName {
   pub(crate) tags: Vec<String>,
 }
 
+/// An image, with its layers kept on disk.
 #[derive(Clone)]
 pub(crate) struct OciImage {
-  pub(crate) data: oci::client::ImageData,
+  pub(crate) manifest: OciImageManifest,
+  /// The digest of the manifest, like "sha256:<hex>".
+  pub(crate) digest: String,
+  pub(crate) config: Vec<u8>,
+  /// The layers, from the bottom one, as listed by the manifest.
+  pub(crate) layers: Vec<blob::Blob>,
   pub(crate) name: ImageName,
+  pub(crate) workdir: PathBuf,
+  pub(crate) fingerprint: Option<String>,
+  /// The digest of the multi-platform index the image was fetched by, when
+  /// it's one of its platform images.
+  pub(crate) index: Option<String>,
 }
 
 impl std::fmt::Debug for OciImage {
   fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
     f.debug_struct("OciImage")
       .field("name", &self.name)
-      .field("data(digest)", &self.data.digest())
+      .field("digest", &self.digest)
       .finish_non_exhaustive()
   }
 }
 
+impl OciImage {
+  pub(crate) fn config(&self) -> Result<serde_json::Value> {
+    serde_json::from_slice(&self.config)
+      .map_err(|err| Error::Unexpected(Box::from(err)))
+  }
+
+  /// The architecture of the image, as declared in its config.
+  pub(crate) fn arch(&self) -> Result<Option<String>> {
+    let config = self.config()?;
+    Ok(config["architecture"].as_str().map(|a| a.to_string()))
+  }
+
+  pub(crate) fn manifest_data(&self) -> Result<Vec<u8>> {
+    serde_json::to_vec(&self.manifest)
+      .map_err(|err| Error::Unexpected(Box::from(err)))
+  }
+
+  /// When the image was created, as declared in its config.
+  pub(crate) fn created_at(&self) -> Option<SystemTime> {
+    created_at(&self.config)
+  }
+
+  /// The size of the content of the image: its config and layers.
+  pub(crate) fn size(&self) -> u64 {
+    self.config.len() as u64
+      + self
+        .layers
+        .iter()
+        .map(|l| l.descriptor.size as u64)
+        .sum::<u64>()
+  }
+}
+
+/// A multi-platform image, with an image per architecture.
+#[derive(Clone, Debug)]
+pub(crate) struct OciIndex {
+  pub(crate) index: index::ImageIndex,
+  pub(crate) digest: String,
+  pub(crate) platforms: Vec<(Arch, OciImage)>,
+  pub(crate) name: ImageName,
+  pub(crate) workdir: PathBuf,
+  pub(crate) fingerprint: Option<String>,
+}
+
+impl OciIndex {
+  /// When the latest of its platform images was created.
+  pub(crate) fn created_at(&self) -> Option<SystemTime> {
+    self
+      .platforms
+      .iter()
+      .filter_map(|(_, i)| i.created_at())
+      .max()
+  }
+
+  /// The size of the content of its platform images.
+  pub(crate) fn size(&self) -> u64 {
+    self.platforms.iter().map(|(_, i)| i.size()).sum()
+  }
+}
+
+/// When the image of the config was created, as declared in it.
+pub(crate) fn created_at(config: &[u8]) -> Option<SystemTime> {
+  let config: serde_json::Value = serde_json::from_slice(config).ok()?;
+  let created = config["created"].as_str()?;
+  let created = chrono::DateTime::parse_from_rfc3339(created).ok()?;
+  Some(created.into())
+}
+
 pub(crate) fn bare_digest(digest: String) -> String {
   digest
     .strip_prefix("sha256:")
     .unwrap_or(digest.as_str())
     .to_string()
 }
+
+pub(crate) fn sha256_digest(data: &[u8]) -> String {
+  format!("sha256:{:x}", Sha256::digest(data))
+}
@@ -0,0 +1,170 @@
+use std::collections::BTreeSet;
+
+use ocilot_core::error::{Error, Result};
+use ocilot_core::oci::{Change, ConfigChanges};
+use serde_json::{json, Map, Value};
+
+/// Applies the changes to the runtime part of the image config.
+pub(crate) fn apply(changes: &ConfigChanges, config: &mut Value) -> Result<()> {
+  if config["config"].is_null() {
+    config["config"] = json!({});
+  }
+  let runtime = config["config"].as_object_mut().ok_or_else(|| {
+    Error::invalid_input("base image runtime config isn't an object")
+  })?;
+  // like in Dockerfile, a new entrypoint drops the inherited command
+  let cmd = match (&changes.entrypoint, &changes.cmd) {
+    (Change::Set(_), Change::Inherit) => &Change::Reset,
+    (_, cmd) => cmd,
+  };
+  list(runtime, "Entrypoint", &changes.entrypoint);
+  list(runtime, "Cmd", cmd);
+  env(runtime, &changes.env);
+  scalar(runtime, "WorkingDir", &changes.workdir);
+  scalar(runtime, "User", &changes.user);
+  scalar(runtime, "StopSignal", &changes.stop_signal);
+  let labels = entries(&changes.labels, |labels| {
+    labels
+      .iter()
+      .map(|(k, v)| (k.clone(), Value::from(v.clone())))
+      .collect()
+  });
+  object(runtime, "Labels", labels);
+  let as_keys = |keys: &BTreeSet<String>| {
+    keys.iter().map(|k| (k.clone(), json!({}))).collect()
+  };
+  object(
+    runtime,
+    "ExposedPorts",
+    entries(&changes.exposed_ports, as_keys),
+  );
+  object(runtime, "Volumes", entries(&changes.volumes, as_keys));
+  Ok(())
+}
+
+fn scalar(
+  runtime: &mut Map<String, Value>,
+  key: &str,
+  change: &Change<String>,
+) {
+  match change {
+    Change::Inherit => {}
+    Change::Reset => {
+      runtime.remove(key);
+    }
+    Change::Set(value) | Change::Append(value) => {
+      runtime.insert(key.to_string(), Value::from(value.clone()));
+    }
+  }
+}
+
+fn list(
+  runtime: &mut Map<String, Value>,
+  key: &str,
+  change: &Change<Vec<String>>,
+) {
+  match change {
+    Change::Inherit => {}
+    Change::Reset => {
+      runtime.remove(key);
+    }
+    Change::Set(values) => {
+      runtime.insert(key.to_string(), json!(values));
+    }
+    Change::Append(values) => {
+      let mut merged = strings(runtime.get(key));
+      merged.extend(values.iter().cloned());
+      runtime.insert(key.to_string(), json!(merged));
+    }
+  }
+}
+
+fn env(
+  runtime: &mut Map<String, Value>,
+  change: &Change<Vec<(String, String)>>,
+) {
+  let key = "Env";
+  let render = |vars: &Vec<(String, String)>| {
+    let vars: Vec<String> =
+      vars.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+    json!(vars)
+  };
+  match change {
+    Change::Inherit => {}
+    Change::Reset => {
+      runtime.remove(key);
+    }
+    Change::Set(vars) => {
+      runtime.insert(key.to_string(), render(vars));
+    }
+    Change::Append(vars) => {
+      let mut merged: Vec<(String, String)> = strings(runtime.get(key))
+        .iter()
+        .map(|var| match var.split_once('=') {
+          Some((k, v)) => (k.to_string(), v.to_string()),
+          None => (var.to_string(), String::new()),
+        })
+        .collect();
+      for (k, v) in vars {
+        match merged.iter_mut().find(|(mk, _)| mk == k) {
+          Some(existing) => existing.1 = v.clone(),
+          None => merged.push((k.clone(), v.clone())),
+        }
+      }
+      runtime.insert(key.to_string(), render(&merged));
+    }
+  }
+}
+
+/// Applies a change to the config value represented as JSON object, like
+/// labels, or volumes.
+fn object(
+  runtime: &mut Map<String, Value>,
+  key: &str,
+  change: Change<Vec<(String, Value)>>,
+) {
+  let render = |entries: Vec<(String, Value)>,
+                mut object: Map<String, Value>| {
+    object.extend(entries);
+    Value::Object(object)
+  };
+  match change {
+    Change::Inherit => {}
+    Change::Reset => {
+      runtime.remove(key);
+    }
+    Change::Set(entries) => {
+      runtime.insert(key.to_string(), render(entries, Map::new()));
+    }
+    Change::Append(entries) => {
+      let base = match runtime.get(key) {
+        Some(Value::Object(base)) => base.clone(),
+        _ => Map::new(),
+      };
+      runtime.insert(key.to_string(), render(entries, base));
+    }
+  }
+}
+
+/// Maps the change into JSON object entries.
+fn entries<T>(
+  change: &Change<T>,
+  f: impl Fn(&T) -> Vec<(String, Value)>,
+) -> Change<Vec<(String, Value)>> {
+  match change {
+    Change::Inherit => Change::Inherit,
+    Change::Reset => Change::Reset,
+    Change::Set(v) => Change::Set(f(v)),
+    Change::Append(v) => Change::Append(f(v)),
+  }
+}
+
+fn strings(value: Option<&Value>) -> Vec<String> {
+  match value {
+    Some(Value::Array(values)) => values
+      .iter()
+      .filter_map(|v| v.as_str().map(|s| s.to_string()))
+      .collect(),
+    _ => Vec::new(),
+  }
+}
@@ -0,0 +1,228 @@
+use std::fs;
+use std::path::Path;
+
+use ocilot_core::build::{Layering, Provenance, Sbom};
+use ocilot_core::error::{Error, Result};
+use ocilot_core::oci::Change;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use tracing::{debug, instrument};
+
+use crate::{cache, sbom, ImageName};
+
+const STATEMENT_TYPE: &str = "https://in-toto.io/Statement/v1";
+const PREDICATE_TYPE: &str = "https://slsa.dev/provenance/v1";
+const BUILD_TYPE: &str = "https://github.com/cardil/ocilot/build/v1";
+pub(crate) const BUILDER_ID: &str = "https://github.com/cardil/ocilot";
+const IN_TOTO: &str = "application/vnd.in-toto+json";
+pub(crate) const OCI_MANIFEST: &str =
+  "application/vnd.oci.image.manifest.v1+json";
+pub(crate) const EMPTY_CONFIG: &str = "application/vnd.oci.empty.v1+json";
+pub(crate) const EMPTY_CONFIG_DIGEST: &str =
+  "sha256:44136fa355b3678a1146ad16f7e8649e94fb4fc21fe77e8310c060f61caaff8a";
+
+/// The image an attestation is about, by its manifest.
+#[derive(Debug)]
+pub(crate) struct Subject<'a> {
+  pub(crate) name: &'a ImageName,
+  pub(crate) media_type: String,
+  /// A bare sha256 digest of the manifest.
+  pub(crate) digest: String,
+  pub(crate) size: usize,
+}
+
+/// Writes the in-toto SLSA provenance statement next to the cached image, as
+/// "provenance.json", and an OCI artifact manifest carrying it, with the
+/// image as its subject, as "provenance.manifest.json". The manifest is meant
+/// to be pushed along the image, as its referrer, once publishing is
+/// implemented. A bill of materials, when requested, is recorded the same
+/// way.
+#[instrument(skip(provenance), level = "trace")]
+pub(crate) fn persist(
+  workdir: &Path,
+  subject: &Subject,
+  provenance: &Provenance,
+) -> Result<()> {
+  let imdir = cache::image_dir(workdir, &subject.digest);
+  fs::create_dir_all(&imdir)?;
+  let statement = statement(subject, provenance);
+  persist_referrer(&imdir, "provenance", IN_TOTO, &statement, subject)?;
+  debug!(image = ?subject.digest, "Provenance attested");
+  if let Some(format) = provenance.build.sbom {
+    let (name, media_type) = sbom::file(format);
+    let document = sbom::document(format, subject, provenance);
+    persist_referrer(&imdir, name, media_type, &document, subject)?;
+    debug!(image = ?subject.digest, "Bill of materials recorded");
+  }
+  Ok(())
+}
+
+/// Writes the document as "{name}.json", and an OCI artifact manifest carrying
+/// it, referring to the subject, as "{name}.manifest.json".
+fn persist_referrer(
+  imdir: &Path,
+  name: &str,
+  media_type: &str,
+  document: &Value,
+  subject: &Subject,
+) -> Result<()> {
+  let document = serde_json::to_vec_pretty(document)
+    .map_err(|err| Error::Unexpected(Box::from(err)))?;
+  fs::write(imdir.join(format!("{}.json", name)), &document)?;
+  let layer = json!({
+    "mediaType": media_type,
+    "digest": crate::sha256_digest(&document),
+    "size": document.len(),
+  });
+  let manifest = serde_json::to_vec(&referrer(media_type, layer, subject))
+    .map_err(|err| Error::Unexpected(Box::from(err)))?;
+  fs::write(imdir.join(format!("{}.manifest.json", name)), manifest)?;
+  Ok(())
+}
+
+/// An OCI artifact manifest of the single layer, referring to the subject.
+pub(crate) fn referrer(
+  artifact_type: &str,
+  layer: Value,
+  subject: &Subject,
+) -> Value {
+  json!({
+    "schemaVersion": 2,
+    "mediaType": OCI_MANIFEST,
+    "artifactType": artifact_type,
+    "config": {
+      "mediaType": EMPTY_CONFIG,
+      "digest": EMPTY_CONFIG_DIGEST,
+      "size": 2,
+    },
+    "layers": [layer],
+    "subject": {
+      "mediaType": subject.media_type,
+      "digest": format!("sha256:{}", subject.digest),
+      "size": subject.size,
+    },
+  })
+}
+
+fn statement(subject: &Subject, provenance: &Provenance) -> Value {
+  let b = &provenance.build;
+  let mut dependencies = vec![json!({
+    "uri": b.base,
+    "digest": { "sha256": provenance.base_digest },
+  })];
+  dependencies.extend(provenance.materials.iter().map(|m| {
+    json!({
+      "uri": format!("file:{}", m.path.display()),
+      "digest": digest_set(&m.digest),
+      "annotations": { "destination": m.to },
+    })
+  }));
+  json!({
+    "_type": STATEMENT_TYPE,
+    "subject": [{
+      "name": subject.name.image,
+      "digest": { "sha256": subject.digest },
+    }],
+    "predicateType": PREDICATE_TYPE,
+    "predicate": {
+      "buildDefinition": {
+        "buildType": BUILD_TYPE,
+        "externalParameters": parameters(provenance),
+        "internalParameters": { "fingerprint": provenance.fingerprint },
+        "resolvedDependencies": dependencies,
+      },
+      "runDetails": {
+        "builder": {
+          "id": BUILDER_ID,
+          "version": { "ocilot": env!("CARGO_PKG_VERSION") },
+        },
+      },
+    },
+  })
+}
+
+/// The invocation parameters of the build.
+fn parameters(provenance: &Provenance) -> Value {
+  let b = &provenance.build;
+  let mut tags: Vec<&String> = b.image.tags.iter().collect();
+  tags.sort();
+  let mut archs: Vec<String> = b.arch.iter().map(|a| a.to_string()).collect();
+  archs.sort();
+  let artifacts: Vec<Value> = b
+    .artifacts
+    .iter()
+    .map(|a| {
+      json!({
+        "arch": a.arch.as_ref().map(|a| a.to_string()),
+        "from": a.from,
+        "to": a.to,
+        "mode": a.mode.map(|m| format!("{:04o}", m)),
+        "owner": a.owner.as_ref().map(|o| o.to_string()),
+        "keepSymlinks": a.keep_symlinks,
+        "exclude": a.exclude,
+        "gitignore": a.gitignore,
+        "layer": a.layer,
+      })
+    })
+    .collect();
+  let symlinks: Vec<Value> = b
+    .symlinks
+    .iter()
+    .map(|s| {
+      json!({
+        "arch": s.arch.as_ref().map(|a| a.to_string()),
+        "path": s.path,
+        "target": s.target,
+      })
+    })
+    .collect();
+  let config = &b.config;
+  json!({
+    "base": b.base,
+    "image": b.image.image,
+    "tags": tags,
+    "arch": archs,
+    "artifacts": artifacts,
+    "symlinks": symlinks,
+    "timestamp": b.timestamp.and_then(|t| {
+      t.duration_since(std::time::UNIX_EPOCH).ok().map(|d| d.as_secs())
+    }),
+    "layering": match b.layering {
+      Layering::Single => "single",
+      Layering::PerArtifact => "per-artifact",
+    },
+    "sbom": b.sbom.map(|format| match format {
+      Sbom::Spdx => "spdx",
+      Sbom::CycloneDx => "cyclonedx",
+    }),
+    "config": {
+      "entrypoint": change(&config.entrypoint),
+      "cmd": change(&config.cmd),
+      "env": change(&config.env),
+      "workdir": change(&config.workdir),
+      "user": change(&config.user),
+      "labels": change(&config.labels),
+      "exposedPorts": change(&config.exposed_ports),
+      "volumes": change(&config.volumes),
+      "stopSignal": change(&config.stop_signal),
+    },
+  })
+}
+
+fn change<T: Serialize>(change: &Change<T>) -> Value {
+  match change {
+    Change::Inherit => json!("inherit"),
+    Change::Reset => json!("reset"),
+    Change::Set(value) => json!({ "set": value }),
+    Change::Append(value) => json!({ "append": value }),
+  }
+}
+
+/// Turns a digest, like "sha256:...", into an in-toto digest set.
+fn digest_set(digest: &str) -> Value {
+  let mut set = Map::new();
+  if let Some((algorithm, hex)) = digest.split_once(':') {
+    set.insert(algorithm.to_string(), Value::from(hex));
+  }
+  Value::Object(set)
+}
@@ -1,60 +1,418 @@
-use crate::{cache, ImageName, OciImage};
+use crate::cache::Origin;
+use crate::construction::{IndexConstruction, OciImageConstruction};
+use crate::index::ImageIndex;
+use crate::store::Store;
+use crate::transfer::Transfers;
+use crate::{
+  blob, cache, index, provenance, signing, ImageName, OciImage, OciIndex,
+};
+use futures::{StreamExt, TryStreamExt};
 use oci_distribution as oci;
+use oci_distribution::client::RegistryOperation;
+use oci_distribution::manifest::{OciImageManifest, OCI_IMAGE_MEDIA_TYPE};
 use oci_distribution::Reference;
 use ocilot_core as core;
+use ocilot_core::build::Provenance;
 use ocilot_core::error::{Error, Result};
-use ocilot_core::oci::{Config, Construction, Image, Input, Registry};
+use ocilot_core::oci::{
+  Config, Construction, Image, Registry, Settings, Signature, SignatureScheme,
+};
 use ocilot_core::Arch;
 use std::collections::HashSet;
-use std::future::Future;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::SystemTime;
-use tokio::runtime::{Handle, Runtime};
-use tracing::{instrument, warn};
+use tracing::instrument;
 
+const ACCEPTED_LAYER_TYPES: [&str; 2] = [
+  "application/vnd.docker.image.rootfs.diff.tar.gzip",
+  "application/vnd.oci.image.layer.v1.tar+gzip",
+];
+
+const ACCEPTED_MANIFEST_TYPES: [&str; 4] = [
+  "application/vnd.docker.distribution.manifest.v2+json",
+  "application/vnd.oci.image.manifest.v1+json",
+  index::DOCKER_MANIFEST_LIST,
+  index::OCI_INDEX,
+];
+
+/// Registries reached over their HTTP API. Their async API runs on the
+/// runtime of the caller, while the [`Registry`] one blocks the caller, see
+/// [`Transfers`].
 #[derive(Debug)]
 pub struct Rest {
   pub config: Box<dyn Config>,
+  pub transfers: Transfers,
 }
 
 impl Registry for Rest {
-  #[instrument(ret, level = "trace")]
-  fn fetch(&self, image_spec: &String) -> Result<Box<dyn Image>> {
+  fn fetch(
+    &self,
+    image_spec: &str,
+    archs: &HashSet<Arch>,
+    progress: &(dyn Fn(usize, usize) + Sync),
+  ) -> Result<Box<dyn Image>> {
+    self
+      .transfers
+      .block_on(Rest::fetch(self, image_spec, archs, progress))
+  }
+
+  fn platforms(&self, image_spec: &str) -> Result<HashSet<Arch>> {
+    self.transfers.block_on(Rest::platforms(self, image_spec))
+  }
+
+  fn digest(&self, image_spec: &str) -> Result<String> {
+    self.transfers.block_on(Rest::digest(self, image_spec))
+  }
+
+  fn push_signature(
+    &self,
+    image_spec: &str,
+    digest: &str,
+    signature: &Signature,
+    scheme: SignatureScheme,
+  ) -> Result<()> {
+    self.transfers.block_on(Rest::push_signature(
+      self, image_spec, digest, signature, scheme,
+    ))
+  }
+
+  fn signatures(
+    &self,
+    image_spec: &str,
+    digest: &str,
+  ) -> Result<Vec<Signature>> {
+    self
+      .transfers
+      .block_on(Rest::signatures(self, image_spec, digest))
+  }
+}
+
+/// The async API, as the one of [`Registry`], for async embedders. The
+/// blobs are transferred on the runtime of the caller, at most
+/// `transfers.concurrency` at once.
+impl Rest {
+  #[instrument(ret, skip(progress), level = "trace")]
+  pub async fn fetch(
+    &self,
+    image_spec: &str,
+    archs: &HashSet<Arch>,
+    progress: &(dyn Fn(usize, usize) + Sync),
+  ) -> Result<Box<dyn Image>> {
+    let progress = Progress::new(progress);
     let config = oci::client::ClientConfig::default();
     let mut cli = oci::client::Client::new(config);
     let imageref = oci::Reference::try_from(image_spec.to_string())
       .map_err(|err| Error::invalid_input_from(Box::from(err)))?;
     let auth = oci::secrets::RegistryAuth::Anonymous;
-    let accpected_media_types =
-      Vec::from(["application/vnd.docker.image.rootfs.diff.tar.gzip"]);
-    let image_data =
-      block_on(cli.pull(&imageref, &auth, accpected_media_types))
-        .map_err(|err| Error::Unexpected(Box::from(err)))?;
-    let image = OciImage {
-      data: image_data,
+    let (raw, digest) = fetch_manifest(&mut cli, &imageref, &auth).await?;
+
+    if !index::is_index(&raw) {
+      let image = self.pull(&mut cli, &imageref, &auth, &progress).await?;
+      cache::persist_image(image.workdir.clone(), &image, Origin::Pulled)?;
+      if archs.len() > 1 {
+        return Err(Error::invalid_input(
+          format!("base image isn't multi-platform: {}", image_spec).as_str(),
+        ));
+      }
+      let arch = image.arch()?;
+      for want in archs {
+        if arch.as_deref() != Some(want.to_string().as_str()) {
+          return Err(Error::invalid_input(
+            format!("base image {} has no {} platform", image_spec, want)
+              .as_str(),
+          ));
+        }
+      }
+      return Ok(Box::new(image) as Box<dyn Image>);
+    }
+
+    let index: ImageIndex = serde_json::from_slice(&raw)
+      .map_err(|err| Error::Unexpected(Box::from(err)))?;
+    // the platform registry clients default to, when none is asked for
+    let default = HashSet::from([Arch::Amd64]);
+    let archs = if archs.is_empty() { &default } else { archs };
+    let mut sorted: Vec<&Arch> = archs.iter().collect();
+    sorted.sort();
+    let mut platforms = Vec::new();
+    for arch in sorted.iter().copied() {
+      let entry = index.find(arch).ok_or_else(|| {
+        Error::invalid_input(
+          format!("base image {} has no {} platform", image_spec, arch)
+            .as_str(),
+        )
+      })?;
+      let platform_ref = Reference::with_digest(
+        imageref.registry().to_string(),
+        imageref.repository().to_string(),
+        entry.digest.clone(),
+      );
+      let mut image =
+        self.pull(&mut cli, &platform_ref, &auth, &progress).await?;
+      image.name = imageref_to_imagename(imageref.clone());
+      image.index = Some(digest.clone());
+      platforms.push((arch.clone(), image));
+    }
+    if platforms.len() == 1 {
+      let (_, image) = platforms.remove(0);
+      cache::persist_image(image.workdir.clone(), &image, Origin::Pulled)?;
+      return Ok(Box::new(image) as Box<dyn Image>);
+    }
+    let workdir = self.config.workdir()?;
+    let media_type = index
+      .media_type
+      .as_deref()
+      .unwrap_or(index::OCI_INDEX)
+      .to_string();
+    // the index is kept as pulled, so it matches its digest
+    Store::open(&workdir)?.put(&media_type, &raw)?;
+    let pulled = OciIndex {
+      index,
+      digest,
+      platforms,
       name: imageref_to_imagename(imageref),
+      workdir,
+      fingerprint: None,
     };
-    cache::persist_image(self.config.workdir()?, &image)?;
-    Ok(Box::new(image) as Box<dyn Image>)
+    cache::persist_index(pulled.workdir.clone(), &pulled, Origin::Pulled)?;
+    Ok(Box::new(pulled) as Box<dyn Image>)
+  }
+
+  #[instrument(ret, level = "trace")]
+  pub async fn platforms(&self, image_spec: &str) -> Result<HashSet<Arch>> {
+    let config = oci::client::ClientConfig::default();
+    let mut cli = oci::client::Client::new(config);
+    let imageref = oci::Reference::try_from(image_spec.to_string())
+      .map_err(|err| Error::invalid_input_from(Box::from(err)))?;
+    let auth = oci::secrets::RegistryAuth::Anonymous;
+    let (raw, _) = fetch_manifest(&mut cli, &imageref, &auth).await?;
+    if index::is_index(&raw) {
+      let index: ImageIndex = serde_json::from_slice(&raw)
+        .map_err(|err| Error::Unexpected(Box::from(err)))?;
+      return Ok(index.archs());
+    }
+    let (_, _, config) = cli
+      .pull_manifest_and_config(&imageref, &auth)
+      .await
+      .map_err(|err| Error::Unexpected(Box::from(err)))?;
+    let config: serde_json::Value = serde_json::from_str(&config)
+      .map_err(|err| Error::Unexpected(Box::from(err)))?;
+    let arch = config["architecture"].as_str().ok_or_else(|| {
+      Error::invalid_input(
+        format!("base image {} has no architecture", image_spec).as_str(),
+      )
+    })?;
+    Ok(HashSet::from([Arch::from_str(arch)?]))
+  }
+
+  #[instrument(ret, level = "trace")]
+  pub async fn digest(&self, image_spec: &str) -> Result<String> {
+    let imageref = oci::Reference::try_from(image_spec.to_string())
+      .map_err(|err| Error::invalid_input_from(Box::from(err)))?;
+    if let Some(digest) = imageref.digest() {
+      return Ok(crate::bare_digest(digest.to_string()));
+    }
+    let config = oci::client::ClientConfig::default();
+    let mut cli = oci::client::Client::new(config);
+    let auth = oci::secrets::RegistryAuth::Anonymous;
+    cli
+      .fetch_manifest_digest(&imageref, &auth)
+      .await
+      .map(crate::bare_digest)
+      .map_err(|err| Error::Unexpected(Box::from(err)))
+  }
+
+  #[instrument(skip(signature), level = "trace")]
+  pub async fn push_signature(
+    &self,
+    image_spec: &str,
+    digest: &str,
+    signature: &Signature,
+    scheme: SignatureScheme,
+  ) -> Result<()> {
+    let config = oci::client::ClientConfig::default();
+    let mut cli = oci::client::Client::new(config);
+    let imageref = oci::Reference::try_from(image_spec.to_string())
+      .map_err(|err| Error::invalid_input_from(Box::from(err)))?;
+    let auth = oci::secrets::RegistryAuth::Anonymous;
+    signing::push(&mut cli, &imageref, &auth, digest, signature, scheme).await
+  }
+
+  #[instrument(ret, level = "trace")]
+  pub async fn signatures(
+    &self,
+    image_spec: &str,
+    digest: &str,
+  ) -> Result<Vec<Signature>> {
+    let config = oci::client::ClientConfig::default();
+    let mut cli = oci::client::Client::new(config);
+    let imageref = oci::Reference::try_from(image_spec.to_string())
+      .map_err(|err| Error::invalid_input_from(Box::from(err)))?;
+    let auth = oci::secrets::RegistryAuth::Anonymous;
+    signing::pull(&mut cli, &imageref, &auth, digest).await
+  }
+
+  async fn pull(
+    &self,
+    cli: &mut oci::client::Client,
+    imageref: &Reference,
+    auth: &oci::secrets::RegistryAuth,
+    progress: &Progress<'_>,
+  ) -> Result<OciImage> {
+    let workdir = self.config.workdir()?;
+    let store = Store::open(&workdir)?;
+    let concurrency = self.transfers.concurrency;
+    let image =
+      pull_image(cli, imageref, auth, &store, concurrency, progress).await?;
+    cache::store_image(&store, &image)?;
+    Ok(image)
   }
 }
 
-fn block_on<F: Future>(future: F) -> F::Output {
-  let (handle, _rt) = get_runtime_handle();
-  handle.block_on(future)
+/// Counts the layers of a base image pulled so far, out of the ones of the
+/// platform manifests pulled so far, reporting them as the fetch progress.
+struct Progress<'a> {
+  report: &'a (dyn Fn(usize, usize) + Sync),
+  pulled: AtomicUsize,
+  total: AtomicUsize,
 }
 
-fn get_runtime_handle() -> (Handle, Option<Runtime>) {
-  match Handle::try_current() {
-    Ok(h) => (h, None),
-    Err(_) => {
-      let rt = Runtime::new().unwrap();
-      (rt.handle().clone(), Some(rt))
+impl<'a> Progress<'a> {
+  fn new(report: &'a (dyn Fn(usize, usize) + Sync)) -> Progress<'a> {
+    Progress {
+      report,
+      pulled: AtomicUsize::new(0),
+      total: AtomicUsize::new(0),
     }
   }
+
+  /// Notes the layers of a platform manifest, yet to be pulled.
+  fn found(&self, layers: usize) {
+    let total = self.total.fetch_add(layers, Ordering::SeqCst) + layers;
+    (self.report)(self.pulled.load(Ordering::SeqCst), total);
+  }
+
+  /// Notes a layer pulled, or found already downloaded.
+  fn pulled(&self) {
+    let pulled = self.pulled.fetch_add(1, Ordering::SeqCst) + 1;
+    (self.report)(pulled, self.total.load(Ordering::SeqCst));
+  }
+}
+
+/// Pulls the image into the store, downloading at most `concurrency` of its
+/// layers at once. The manifest and config are checked against their
+/// digests, as are the layers, streamed. The manifest is stored as pulled,
+/// so it matches its digest.
+async fn pull_image(
+  cli: &mut oci::client::Client,
+  imageref: &Reference,
+  auth: &oci::secrets::RegistryAuth,
+  store: &Store,
+  concurrency: usize,
+  progress: &Progress<'_>,
+) -> Result<OciImage> {
+  cli
+    .auth(imageref, auth, RegistryOperation::Pull)
+    .await
+    .map_err(|err| Error::Unexpected(Box::from(err)))?;
+  let (_, digest, config) = cli
+    .pull_manifest_and_config(imageref, auth)
+    .await
+    .map_err(|err| Error::Unexpected(Box::from(err)))?;
+  let pinned = Reference::with_digest(
+    imageref.registry().to_string(),
+    imageref.repository().to_string(),
+    imageref.digest().unwrap_or(&digest).to_string(),
+  );
+  let (raw, _) = cli
+    .pull_manifest_raw(&pinned, auth, &ACCEPTED_MANIFEST_TYPES)
+    .await
+    .map_err(|err| Error::Unexpected(Box::from(err)))?;
+  let digest = check_digest(imageref, &raw, &digest)?;
+  // the manifest is read from what's checked, rather than taken as parsed
+  let manifest: OciImageManifest = serde_json::from_slice(&raw)
+    .map_err(|err| Error::Unexpected(Box::from(err)))?;
+  if crate::sha256_digest(config.as_bytes()) != manifest.config.digest {
+    return Err(Error::invalid_input(&format!(
+      "config of image {} doesn't match its digest {}",
+      imageref, manifest.config.digest
+    )));
+  }
+  let unsupported = manifest
+    .layers
+    .iter()
+    .find(|l| !ACCEPTED_LAYER_TYPES.contains(&l.media_type.as_str()));
+  if let Some(layer) = unsupported {
+    return Err(Error::invalid_input(&format!(
+      "image {} has a layer of unsupported type: {}",
+      imageref, layer.media_type
+    )));
+  }
+  let media_type = manifest
+    .media_type
+    .clone()
+    .unwrap_or_else(|| OCI_IMAGE_MEDIA_TYPE.to_string());
+  store.put(&media_type, &raw)?;
+  progress.found(manifest.layers.len());
+  let blobs = store.blobs();
+  let cli = &*cli;
+  let downloads: Vec<_> = manifest
+    .layers
+    .iter()
+    .map(|descriptor| blob::download(cli, imageref, descriptor, &blobs))
+    .collect();
+  let layers = futures::stream::iter(downloads)
+    .buffered(concurrency)
+    .inspect_ok(|_| progress.pulled())
+    .try_collect::<Vec<_>>()
+    .await?;
+  Ok(OciImage {
+    manifest,
+    digest,
+    config: config.into_bytes(),
+    layers,
+    name: imageref_to_imagename(imageref.clone()),
+    workdir: store.root().to_path_buf(),
+    fingerprint: None,
+    index: None,
+  })
+}
+
+/// Fetches the raw manifest of the image, be it an index, along with its
+/// digest, once checked.
+pub(crate) async fn fetch_manifest(
+  cli: &mut oci::client::Client,
+  imageref: &Reference,
+  auth: &oci::secrets::RegistryAuth,
+) -> Result<(Vec<u8>, String)> {
+  let (raw, digest) = cli
+    .pull_manifest_raw(imageref, auth, &ACCEPTED_MANIFEST_TYPES)
+    .await
+    .map_err(|err| Error::Unexpected(Box::from(err)))?;
+  let digest = check_digest(imageref, &raw, &digest)?;
+  Ok((raw, digest))
+}
+
+/// Checks the raw manifest pulled for the reference is digested as the
+/// reference pins it, or else as the registry tells, giving the digest.
+fn check_digest(
+  imageref: &Reference,
+  raw: &[u8],
+  told: &str,
+) -> Result<String> {
+  let want = imageref.digest().unwrap_or(told);
+  let got = crate::sha256_digest(raw);
+  if got != want {
+    return Err(Error::invalid_input(&format!(
+      "manifest of image {} doesn't match its digest {}, it's {}",
+      imageref, want, got
+    )));
+  }
+  Ok(got)
 }
 
 #[instrument(ret, level = "trace")]
-fn imageref_to_imagename(imageref: Reference) -> ImageName {
+pub(crate) fn imageref_to_imagename(imageref: Reference) -> ImageName {
   let mut registry = imageref.registry();
   const DOCKER_REGISTRY: &str = "docker.io";
   if registry == "" {
@@ -74,9 +432,48 @@ fn imageref_to_imagename(imageref: Reference) -> ImageName {
   }
 }
 
+impl OciImage {
+  /// The image, as the subject of its attestations.
+  fn subject(&self) -> Result<provenance::Subject<'_>> {
+    let manifest = self.manifest_data()?;
+    let media_type = self
+      .manifest
+      .media_type
+      .clone()
+      .unwrap_or_else(|| OCI_IMAGE_MEDIA_TYPE.to_string());
+    Ok(provenance::Subject {
+      name: &self.name,
+      media_type,
+      digest: self.digest(),
+      size: manifest.len(),
+    })
+  }
+}
+
+impl OciIndex {
+  /// The index, as the subject of its attestations.
+  fn subject(&self) -> Result<provenance::Subject<'_>> {
+    let index = serde_json::to_vec(&self.index)
+      .map_err(|err| Error::Unexpected(Box::from(err)))?;
+    let media_type =
+      self.index.media_type.as_deref().unwrap_or(index::OCI_INDEX);
+    Ok(provenance::Subject {
+      name: &self.name,
+      media_type: media_type.to_string(),
+      digest: self.digest(),
+      size: index.len(),
+    })
+  }
+}
+
 impl Image for OciImage {
   fn digest(&self) -> String {
-    crate::bare_digest(self.data.digest())
+    crate::bare_digest(self.digest.clone())
+  }
+
+  fn fetched_digest(&self) -> String {
+    let fetched = self.index.as_ref().unwrap_or(&self.digest);
+    crate::bare_digest(fetched.clone())
   }
 
   fn name(&self) -> core::build::ImageName {
@@ -87,48 +484,152 @@ impl Image for OciImage {
   }
 
   fn created(&self) -> SystemTime {
-    warn!("Not yet implemented!");
-    SystemTime::UNIX_EPOCH
+    self.created_at().unwrap_or(SystemTime::UNIX_EPOCH)
   }
 
-  fn construct_new(&self, _: &HashSet<Arch>) -> Box<dyn Construction> {
-    Box::new(OciImageConstruction {
-      digest: self.digest(),
-    })
+  fn fingerprint(&self) -> Option<String> {
+    self.fingerprint.clone()
+  }
+
+  fn platforms(&self) -> Result<HashSet<Arch>> {
+    match self.arch()? {
+      Some(arch) => Ok(HashSet::from([Arch::from_str(&arch)?])),
+      None => Ok(HashSet::new()),
+    }
+  }
+
+  fn attest(&self, provenance: &Provenance) -> Result<()> {
+    provenance::persist(&self.workdir, &self.subject()?, provenance)
+  }
+
+  fn sign(&self, signature: &Signature) -> Result<()> {
+    signing::persist(&self.workdir, &self.subject()?, signature)
+  }
+
+  fn construct_new(&self, settings: &Settings) -> Box<dyn Construction> {
+    Box::new(OciImageConstruction::new(self, settings))
   }
 }
 
-#[derive(Debug)]
-struct OciImageConstruction {
-  digest: String,
+impl Image for OciIndex {
+  fn digest(&self) -> String {
+    crate::bare_digest(self.digest.clone())
+  }
+
+  fn name(&self) -> core::build::ImageName {
+    core::build::ImageName {
+      image: self.name.image.to_string(),
+      tags: HashSet::from_iter(self.name.tags.iter().map(|t| t.to_string())),
+    }
+  }
+
+  fn created(&self) -> SystemTime {
+    self.created_at().unwrap_or(SystemTime::UNIX_EPOCH)
+  }
+
+  fn fingerprint(&self) -> Option<String> {
+    self.fingerprint.clone()
+  }
+
+  fn platforms(&self) -> Result<HashSet<Arch>> {
+    Ok(
+      self
+        .platforms
+        .iter()
+        .map(|(arch, _)| arch.clone())
+        .collect(),
+    )
+  }
+
+  fn attest(&self, provenance: &Provenance) -> Result<()> {
+    provenance::persist(&self.workdir, &self.subject()?, provenance)
+  }
+
+  fn sign(&self, signature: &Signature) -> Result<()> {
+    signing::persist(&self.workdir, &self.subject()?, signature)
+  }
+
+  fn construct_new(&self, settings: &Settings) -> Box<dyn Construction> {
+    Box::new(IndexConstruction::new(self, settings))
+  }
 }
 
-impl Construction for OciImageConstruction {
-  #[instrument(ret, level = "trace")]
-  fn add(&self, _: Vec<Input>) {
-    warn!("Not yet implemented!");
+#[cfg(test)]
+mod tests {
+  use std::sync::Mutex;
+
+  use oci_distribution::Reference;
+  use ocilot_core::error::Error;
+
+  use crate::config::Config;
+  use crate::registry::{check_digest, Progress, Rest};
+  use crate::transfer::Transfers;
+
+  #[test]
+  fn progress() {
+    let reported = Mutex::new(Vec::new());
+    let report = |pulled, total| reported.lock().unwrap().push((pulled, total));
+    let progress = Progress::new(&report);
+
+    progress.found(2);
+    progress.pulled();
+    progress.found(1);
+    progress.pulled();
+    progress.pulled();
+
+    assert_eq!(
+      *reported.lock().unwrap(),
+      vec![(0, 2), (1, 2), (1, 3), (2, 3), (3, 3)]
+    );
   }
 
-  #[instrument(ret, level = "trace")]
-  fn build(
-    &self,
-    name: &ocilot_core::build::ImageName,
-  ) -> Result<Box<dyn Image>> {
-    warn!("Not yet implemented!");
-    Ok(Box::from(OciImage {
-      data: oci_distribution::client::ImageData {
-        layers: vec![],
-        digest: Some(self.digest.to_string()),
-        config: oci_distribution::client::Config {
-          data: vec![],
-          media_type: "Not yet implemented!".to_string(),
-        },
-        manifest: None,
-      },
-      name: ImageName {
-        image: name.image.to_string(),
-        tags: name.tags.iter().map(|t| t.to_string()).collect(),
-      },
-    }) as Box<dyn Image>)
+  #[test]
+  fn checked_digest() {
+    let raw = br#"{"schemaVersion":2}"#;
+    let digest = crate::sha256_digest(raw);
+    let other = crate::sha256_digest(b"other");
+    let reference = |digest: Option<&str>| match digest {
+      Some(digest) => Reference::with_digest(
+        "quay.io".to_string(),
+        "acme/base".to_string(),
+        digest.to_string(),
+      ),
+      None => Reference::with_tag(
+        "quay.io".to_string(),
+        "acme/base".to_string(),
+        "v1".to_string(),
+      ),
+    };
+
+    let pinned = check_digest(&reference(Some(&digest)), raw, &other);
+    let told = check_digest(&reference(None), raw, &digest);
+    let tampered = check_digest(&reference(Some(&other)), raw, &other);
+    let lied = check_digest(&reference(None), raw, &other);
+
+    assert_eq!(pinned.unwrap(), digest);
+    assert_eq!(told.unwrap(), digest);
+    for res in [tampered, lied] {
+      assert!(
+        matches!(&res, Err(Error::InvalidInput { message, .. }) if message.contains(&digest)),
+        "{:?}",
+        res
+      );
+    }
+  }
+
+  #[tokio::test]
+  async fn awaited() {
+    let rest = Rest {
+      config: Box::new(Config {
+        workdir: std::env::temp_dir().join("ocilot-registry-awaited"),
+      }),
+      transfers: Transfers::new(1).unwrap(),
+    };
+    let digest = crate::sha256_digest(b"base");
+    let image = format!("quay.io/acme/base@{}", digest);
+
+    let got = rest.digest(&image).await.unwrap();
+
+    assert_eq!(got, crate::bare_digest(digest));
   }
 }
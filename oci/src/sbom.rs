@@ -0,0 +1,371 @@
+use std::time::SystemTime;
+
+use ocilot_core::build::{Provenance, Sbom};
+use ocilot_core::fs::Package;
+use serde_json::{json, Value};
+
+use crate::provenance::{Subject, BUILDER_ID};
+
+const SPDX: &str = "application/spdx+json";
+const CYCLONEDX: &str = "application/vnd.cyclonedx+json";
+
+/// The base name of the bill of materials file, and its media type.
+pub(crate) fn file(format: Sbom) -> (&'static str, &'static str) {
+  match format {
+    Sbom::Spdx => ("sbom.spdx", SPDX),
+    Sbom::CycloneDx => ("sbom.cdx", CYCLONEDX),
+  }
+}
+
+/// Describes the image as a bill of materials: the base image it depends on,
+/// the files added to it, and the Rust crates compiled into them.
+pub(crate) fn document(
+  format: Sbom,
+  subject: &Subject,
+  provenance: &Provenance,
+) -> Value {
+  match format {
+    Sbom::Spdx => spdx(subject, provenance),
+    Sbom::CycloneDx => cyclonedx(subject, provenance),
+  }
+}
+
+fn spdx(subject: &Subject, provenance: &Provenance) -> Value {
+  let b = &provenance.build;
+  let mut packages = vec![
+    json!({
+      "SPDXID": "SPDXRef-Image",
+      "name": subject.name.image,
+      "versionInfo": format!("sha256:{}", subject.digest),
+      "downloadLocation": "NOASSERTION",
+      "primaryPackagePurpose": "CONTAINER",
+      "checksums": [
+        { "algorithm": "SHA256", "checksumValue": subject.digest },
+      ],
+    }),
+    json!({
+      "SPDXID": "SPDXRef-Base",
+      "name": b.base,
+      "versionInfo": format!("sha256:{}", provenance.base_digest),
+      "downloadLocation": "NOASSERTION",
+      "primaryPackagePurpose": "CONTAINER",
+      "checksums": [
+        { "algorithm": "SHA256", "checksumValue": provenance.base_digest },
+      ],
+    }),
+  ];
+  let mut files = Vec::new();
+  let mut relationships = vec![
+    relationship("SPDXRef-DOCUMENT", "DESCRIBES", "SPDXRef-Image"),
+    relationship("SPDXRef-Image", "DEPENDS_ON", "SPDXRef-Base"),
+  ];
+  for (i, m) in provenance.materials.iter().enumerate() {
+    let file_id = format!("SPDXRef-File-{}", i);
+    let mut checksums =
+      vec![json!({ "algorithm": "SHA256", "checksumValue": hex(&m.digest) })];
+    if let Some(sha1) = &m.sha1 {
+      checksums.push(json!({ "algorithm": "SHA1", "checksumValue": sha1 }));
+    }
+    files.push(json!({
+      "SPDXID": file_id,
+      "fileName": m.to,
+      "checksums": checksums,
+      "comment": format!("{} bytes, from {}", m.size, m.path.display()),
+    }));
+    relationships.push(relationship("SPDXRef-Image", "CONTAINS", &file_id));
+    for (j, p) in m.packages.iter().enumerate() {
+      let package_id = format!("SPDXRef-File-{}-Crate-{}", i, j);
+      packages.push(json!({
+        "SPDXID": package_id,
+        "name": p.name,
+        "versionInfo": p.version,
+        "downloadLocation": "NOASSERTION",
+        "primaryPackagePurpose": "LIBRARY",
+        "externalRefs": [{
+          "referenceCategory": "PACKAGE-MANAGER",
+          "referenceType": "purl",
+          "referenceLocator": purl(p),
+        }],
+      }));
+      relationships.push(relationship(&file_id, "CONTAINS", &package_id));
+    }
+  }
+  json!({
+    "spdxVersion": "SPDX-2.3",
+    "dataLicense": "CC0-1.0",
+    "SPDXID": "SPDXRef-DOCUMENT",
+    "name": subject.name.image,
+    "documentNamespace": format!(
+      "{}/spdx/{}/sha256-{}",
+      BUILDER_ID, subject.name.image, subject.digest
+    ),
+    "creationInfo": {
+      "created": created(provenance),
+      "creators": [format!("Tool: ocilot-{}", env!("CARGO_PKG_VERSION"))],
+    },
+    "packages": packages,
+    "files": files,
+    "relationships": relationships,
+  })
+}
+
+fn relationship(element: &str, kind: &str, related: &str) -> Value {
+  json!({
+    "spdxElementId": element,
+    "relationshipType": kind,
+    "relatedSpdxElement": related,
+  })
+}
+
+fn cyclonedx(subject: &Subject, provenance: &Provenance) -> Value {
+  let b = &provenance.build;
+  let mut components = vec![json!({
+    "type": "container",
+    "bom-ref": "base",
+    "name": b.base,
+    "version": format!("sha256:{}", provenance.base_digest),
+    "hashes": [{ "alg": "SHA-256", "content": provenance.base_digest }],
+  })];
+  for (i, m) in provenance.materials.iter().enumerate() {
+    let crates: Vec<Value> = m
+      .packages
+      .iter()
+      .enumerate()
+      .map(|(j, p)| {
+        json!({
+          "type": "library",
+          "bom-ref": format!("file-{}-crate-{}", i, j),
+          "name": p.name,
+          "version": p.version,
+          "purl": purl(p),
+        })
+      })
+      .collect();
+    components.push(json!({
+      "type": "file",
+      "bom-ref": format!("file-{}", i),
+      "name": m.to,
+      "hashes": [{ "alg": "SHA-256", "content": hex(&m.digest) }],
+      "properties": [
+        { "name": "ocilot:source", "value": m.path.display().to_string() },
+        { "name": "ocilot:size", "value": m.size.to_string() },
+      ],
+      "components": crates,
+    }));
+  }
+  json!({
+    "bomFormat": "CycloneDX",
+    "specVersion": "1.5",
+    "version": 1,
+    "metadata": {
+      "timestamp": created(provenance),
+      "tools": {
+        "components": [{
+          "type": "application",
+          "name": "ocilot",
+          "version": env!("CARGO_PKG_VERSION"),
+        }],
+      },
+      "component": {
+        "type": "container",
+        "bom-ref": "image",
+        "name": subject.name.image,
+        "version": format!("sha256:{}", subject.digest),
+        "hashes": [{ "alg": "SHA-256", "content": subject.digest }],
+      },
+    },
+    "components": components,
+    "dependencies": [{ "ref": "image", "dependsOn": ["base"] }],
+  })
+}
+
+/// The package URL of a crate. Only the crates.io ones are resolvable.
+fn purl(p: &Package) -> String {
+  let purl = format!("pkg:cargo/{}@{}", p.name, p.version);
+  if p.source == "crates.io" {
+    purl
+  } else {
+    format!("{}?source={}", purl, p.source)
+  }
+}
+
+/// The hex part of a "sha256:..." digest.
+fn hex(digest: &str) -> &str {
+  digest.split_once(':').map_or(digest, |(_, hex)| hex)
+}
+
+/// The creation time of the document, fixed along a reproducible build.
+fn created(provenance: &Provenance) -> String {
+  let created = provenance.build.timestamp.unwrap_or_else(SystemTime::now);
+  chrono::DateTime::<chrono::Utc>::from(created)
+    .to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::HashSet;
+  use std::path::PathBuf;
+  use std::time::{Duration, SystemTime};
+
+  use ocilot_core::build::{Build, Material, Provenance, Sbom};
+  use ocilot_core::fs::Package;
+  use serde_json::json;
+
+  use crate::provenance::Subject;
+  use crate::sbom::document;
+  use crate::ImageName;
+
+  fn provenance() -> Provenance {
+    Provenance {
+      build: Build {
+        base: "gcr.io/distroless/static-debian11".to_string(),
+        artifacts: vec![],
+        image: ocilot_core::build::ImageName {
+          image: "quay.io/acme/app".to_string(),
+          tags: HashSet::new(),
+        },
+        arch: HashSet::new(),
+        timestamp: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(60)),
+        config: Default::default(),
+        symlinks: vec![],
+        layering: Default::default(),
+        sbom: Some(Sbom::Spdx),
+      },
+      base_digest: "b4se".to_string(),
+      materials: vec![Material {
+        path: PathBuf::from("target/acme"),
+        digest: "sha256:f11e".to_string(),
+        to: "/usr/bin/acme".to_string(),
+        size: 42,
+        sha1: Some("5ha1".to_string()),
+        packages: vec![Package {
+          name: "serde".to_string(),
+          version: "1.0.193".to_string(),
+          source: "crates.io".to_string(),
+        }],
+      }],
+      fingerprint: "f1n9".to_string(),
+    }
+  }
+
+  fn subject(name: &ImageName) -> Subject<'_> {
+    Subject {
+      name,
+      media_type: "application/vnd.oci.image.index.v1+json".to_string(),
+      digest: "1ma9e".to_string(),
+      size: 1024,
+    }
+  }
+
+  #[test]
+  fn spdx() {
+    let provenance = provenance();
+    let name = ImageName {
+      image: "quay.io/acme/app".to_string(),
+      tags: vec![],
+    };
+    let subject = subject(&name);
+
+    let got = document(Sbom::Spdx, &subject, &provenance);
+
+    assert_eq!(got["spdxVersion"], "SPDX-2.3");
+    assert_eq!(got["creationInfo"]["created"], "1970-01-01T00:01:00Z");
+    let ids: Vec<&str> = got["packages"]
+      .as_array()
+      .unwrap()
+      .iter()
+      .map(|p| p["SPDXID"].as_str().unwrap())
+      .collect();
+    assert_eq!(
+      ids,
+      vec!["SPDXRef-Image", "SPDXRef-Base", "SPDXRef-File-0-Crate-0"]
+    );
+    assert_eq!(
+      got["packages"][2]["externalRefs"][0]["referenceLocator"],
+      "pkg:cargo/serde@1.0.193"
+    );
+    assert_eq!(
+      got["files"],
+      json!([{
+        "SPDXID": "SPDXRef-File-0",
+        "fileName": "/usr/bin/acme",
+        "checksums": [
+          { "algorithm": "SHA256", "checksumValue": "f11e" },
+          { "algorithm": "SHA1", "checksumValue": "5ha1" },
+        ],
+        "comment": "42 bytes, from target/acme",
+      }])
+    );
+    let relationships: Vec<(&str, &str, &str)> = got["relationships"]
+      .as_array()
+      .unwrap()
+      .iter()
+      .map(|r| {
+        (
+          r["spdxElementId"].as_str().unwrap(),
+          r["relationshipType"].as_str().unwrap(),
+          r["relatedSpdxElement"].as_str().unwrap(),
+        )
+      })
+      .collect();
+    assert_eq!(
+      relationships,
+      vec![
+        ("SPDXRef-DOCUMENT", "DESCRIBES", "SPDXRef-Image"),
+        ("SPDXRef-Image", "DEPENDS_ON", "SPDXRef-Base"),
+        ("SPDXRef-Image", "CONTAINS", "SPDXRef-File-0"),
+        ("SPDXRef-File-0", "CONTAINS", "SPDXRef-File-0-Crate-0"),
+      ]
+    );
+  }
+
+  #[test]
+  fn cyclonedx() {
+    let provenance = provenance();
+    let name = ImageName {
+      image: "quay.io/acme/app".to_string(),
+      tags: vec![],
+    };
+    let subject = subject(&name);
+
+    let got = document(Sbom::CycloneDx, &subject, &provenance);
+
+    assert_eq!(got["bomFormat"], "CycloneDX");
+    assert_eq!(got["specVersion"], "1.5");
+    assert_eq!(got["metadata"]["timestamp"], "1970-01-01T00:01:00Z");
+    assert_eq!(got["metadata"]["component"]["version"], "sha256:1ma9e");
+    assert_eq!(
+      got["components"],
+      json!([
+        {
+          "type": "container",
+          "bom-ref": "base",
+          "name": "gcr.io/distroless/static-debian11",
+          "version": "sha256:b4se",
+          "hashes": [{ "alg": "SHA-256", "content": "b4se" }],
+        },
+        {
+          "type": "file",
+          "bom-ref": "file-0",
+          "name": "/usr/bin/acme",
+          "hashes": [{ "alg": "SHA-256", "content": "f11e" }],
+          "properties": [
+            { "name": "ocilot:source", "value": "target/acme" },
+            { "name": "ocilot:size", "value": "42" },
+          ],
+          "components": [{
+            "type": "library",
+            "bom-ref": "file-0-crate-0",
+            "name": "serde",
+            "version": "1.0.193",
+            "purl": "pkg:cargo/serde@1.0.193",
+          }],
+        },
+      ])
+    );
+    assert_eq!(
+      got["dependencies"],
+      json!([{ "ref": "image", "dependsOn": ["base"] }])
+    );
+  }
+}
@@ -0,0 +1,669 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use oci_distribution::manifest::{OciDescriptor, OciImageManifest};
+use ocilot_core::error::{Error, Result};
+use tracing::{debug, info, instrument, warn};
+
+use crate::blob;
+use crate::cache::{self, Cached, Origin};
+use crate::index::{self, ImageIndex, IndexEntry};
+
+const LAYOUT_FILE: &str = "oci-layout";
+const LAYOUT: &str = r#"{"imageLayoutVersion":"1.0.0"}"#;
+const INDEX_FILE: &str = "index.json";
+/// Serializes the changes to the index of the store, and its migration and
+/// garbage collection, among the builds of every process sharing the cache.
+const LOCK_FILE: &str = "index.json.lock";
+/// How long an unreferenced blob is kept: a younger one may be written, or
+/// stored but not yet indexed, by a build running meanwhile.
+const GRACE_PERIOD: Duration = Duration::from_secs(24 * 60 * 60);
+/// The reference of an image within the layout, as understood by other tools,
+/// like `skopeo copy oci:<cache>:<ref>`.
+pub(crate) const REF_NAME: &str = "org.opencontainers.image.ref.name";
+pub(crate) const NAME: &str = "dev.ocilot.image.name";
+/// The tags of the image, separated by commas.
+pub(crate) const TAGS: &str = "dev.ocilot.image.tags";
+pub(crate) const FINGERPRINT: &str = "dev.ocilot.fingerprint";
+/// Either "pulled" or "built".
+pub(crate) const ORIGIN: &str = "dev.ocilot.image.origin";
+pub(crate) const CONFIG: &str = "dev.ocilot.image.config";
+/// The size of the content of the image, in bytes.
+pub(crate) const SIZE: &str = "dev.ocilot.image.size";
+pub(crate) const CREATED: &str = "org.opencontainers.image.created";
+
+/// The content-addressed store of the cache, shared by all of its images.
+///
+/// It's laid out as an OCI image layout: every blob, like a layer, a config
+/// or a manifest, is stored once, as "blobs/sha256/<hex>", and "index.json"
+/// lists the images, by their manifest digests. The per-image directories
+/// only hold what ocilot adds to an image, like its attestations.
+#[derive(Debug, Clone)]
+pub(crate) struct Store {
+  root: PathBuf,
+}
+
+/// The blobs removed by a garbage collection.
+#[derive(PartialEq, Eq, Debug, Default, Clone, Copy)]
+pub struct Collected {
+  pub blobs: usize,
+  pub bytes: u64,
+}
+
+impl Store {
+  /// Opens the store of the work directory, creating it on first use. The
+  /// images of a cache predating the store are moved into it.
+  pub(crate) fn open(workdir: &Path) -> Result<Store> {
+    let store = Store {
+      root: workdir.to_path_buf(),
+    };
+    if !store.root.join(LAYOUT_FILE).exists() {
+      fs::create_dir_all(store.blobs())?;
+      let _lock = store.lock()?;
+      if !store.root.join(LAYOUT_FILE).exists() {
+        store.migrate()?;
+        fs::write(store.root.join(LAYOUT_FILE), LAYOUT)?;
+      }
+    }
+    Ok(store)
+  }
+
+  /// The work directory the store is laid out in.
+  pub(crate) fn root(&self) -> &Path {
+    &self.root
+  }
+
+  /// The directory of the blobs, named by their bare digests.
+  pub(crate) fn blobs(&self) -> PathBuf {
+    self.root.join("blobs").join("sha256")
+  }
+
+  pub(crate) fn blob_path(&self, digest: &str) -> PathBuf {
+    self.blobs().join(crate::bare_digest(digest.to_string()))
+  }
+
+  /// Stores the content as a blob, unless it's there already.
+  pub(crate) fn put(
+    &self,
+    media_type: &str,
+    data: &[u8],
+  ) -> Result<OciDescriptor> {
+    let descriptor = OciDescriptor {
+      media_type: media_type.to_string(),
+      digest: crate::sha256_digest(data),
+      size: data.len() as i64,
+      urls: None,
+      annotations: None,
+    };
+    Ok(blob::write(&self.blobs(), descriptor, data)?.descriptor)
+  }
+
+  /// Lists the images of the store. None, before the first one is added.
+  pub(crate) fn images(&self) -> Result<Vec<IndexEntry>> {
+    let path = self.root.join(INDEX_FILE);
+    if !path.exists() {
+      return Ok(vec![]);
+    }
+    let index: ImageIndex = serde_json::from_slice(&fs::read(path)?)
+      .map_err(|err| Error::Unexpected(Box::from(err)))?;
+    Ok(index.manifests)
+  }
+
+  /// Adds the image to the index of the store, replacing the entry of the
+  /// same manifest digest and name, if any, or an unnamed one, as migrated.
+  /// The tags of the replaced entry are kept, while the ones of the image
+  /// move to it, from the other images of its name. An image left with no
+  /// tags is superseded, and dropped, so its blobs get collected.
+  pub(crate) fn add(&self, entry: IndexEntry) -> Result<()> {
+    let _lock = self.lock()?;
+    let mut added = cached(&entry);
+    let moved = added.tags.clone();
+    let mut manifests = Vec::new();
+    for m in self.images()? {
+      let mut other = cached(&m);
+      if m.digest == entry.digest
+        && (other.image.is_empty() || other.image == added.image)
+      {
+        for tag in other.tags {
+          if !added.tags.contains(&tag) {
+            added.tags.push(tag);
+          }
+        }
+        continue;
+      }
+      if other.image != added.image
+        || !other.tags.iter().any(|t| moved.contains(t))
+      {
+        manifests.push(m);
+        continue;
+      }
+      other.tags.retain(|t| !moved.contains(t));
+      if other.tags.is_empty() {
+        debug!(image = ?m.digest, "Cached image superseded");
+        continue;
+      }
+      manifests.push(retagged(&m, &other));
+    }
+    manifests.push(retagged(&entry, &added));
+    self.write_index(manifests)
+  }
+
+  /// Locks the index, for the builds of every process, until the lock file
+  /// returned is closed.
+  fn lock(&self) -> Result<File> {
+    let file = File::options()
+      .create(true)
+      .truncate(false)
+      .write(true)
+      .open(self.root.join(LOCK_FILE))?;
+    file.lock()?;
+    Ok(file)
+  }
+
+  fn write_index(&self, manifests: Vec<IndexEntry>) -> Result<()> {
+    let index = ImageIndex {
+      schema_version: 2,
+      media_type: Some(index::OCI_INDEX.to_string()),
+      manifests,
+      annotations: None,
+    };
+    let data = serde_json::to_vec_pretty(&index)
+      .map_err(|err| Error::Unexpected(Box::from(err)))?;
+    let partial = self.root.join(format!("{}.partial", INDEX_FILE));
+    fs::write(&partial, data)?;
+    fs::rename(partial, self.root.join(INDEX_FILE))?;
+    Ok(())
+  }
+
+  /// Counts the references to each blob, made by the images of the index:
+  /// their manifests, and the configs and layers those list. The manifests
+  /// of a multi-platform image count as referred to by its index.
+  pub(crate) fn references(&self) -> Result<HashMap<String, usize>> {
+    let mut counts = HashMap::new();
+    let mut pending: Vec<String> =
+      self.images()?.into_iter().map(|m| m.digest).collect();
+    while let Some(digest) = pending.pop() {
+      let count = counts
+        .entry(crate::bare_digest(digest.clone()))
+        .or_default();
+      *count += 1;
+      if *count > 1 {
+        // the blobs it lists are counted already
+        continue;
+      }
+      let raw = match fs::read(self.blob_path(&digest)) {
+        Ok(raw) => raw,
+        Err(err) => {
+          warn!(digest = ?digest, error = %err, "Image manifest missing");
+          continue;
+        }
+      };
+      if index::is_index(&raw) {
+        let index: ImageIndex = serde_json::from_slice(&raw)
+          .map_err(|err| Error::Unexpected(Box::from(err)))?;
+        pending.extend(index.manifests.into_iter().map(|m| m.digest));
+        continue;
+      }
+      let manifest: OciImageManifest = serde_json::from_slice(&raw)
+        .map_err(|err| Error::Unexpected(Box::from(err)))?;
+      for blob in manifest.layers.iter().chain([&manifest.config]) {
+        *counts
+          .entry(crate::bare_digest(blob.digest.clone()))
+          .or_default() += 1;
+      }
+    }
+    Ok(counts)
+  }
+
+  /// Removes the blobs no image of the index refers to, along with any
+  /// leftovers of interrupted writes. The ones modified within the grace
+  /// period are kept, as builds of other processes may still be using them.
+  #[instrument(ret, level = "trace")]
+  pub(crate) fn collect_garbage(&self) -> Result<Collected> {
+    let _lock = self.lock()?;
+    let references = self.references()?;
+    let now = SystemTime::now();
+    let mut collected = Collected::default();
+    for entry in fs::read_dir(self.blobs())? {
+      let entry = entry?;
+      let name = entry.file_name().to_string_lossy().to_string();
+      let meta = entry.metadata()?;
+      let young = now
+        .duration_since(meta.modified()?)
+        .map_or(true, |age| age < GRACE_PERIOD);
+      if references.contains_key(&name) || young {
+        continue;
+      }
+      collected.bytes += meta.len();
+      collected.blobs += 1;
+      fs::remove_file(entry.path())?;
+      debug!(blob = ?name, "Blob collected");
+    }
+    Ok(collected)
+  }
+
+  /// Moves the blobs of the per-image directories, written by the older
+  /// caches, into the store, and lists their images within the index. Their
+  /// names weren't recorded, so they're found by fingerprint and digest only.
+  /// Their old records are removed once the index is written, so a migration
+  /// interrupted before is run again.
+  fn migrate(&self) -> Result<()> {
+    let images = self.root.join("images");
+    if !images.exists() {
+      return Ok(());
+    }
+    let mut manifests = self.images()?;
+    let mut migrated = Vec::new();
+    for prefix in fs::read_dir(images)? {
+      let prefix = prefix?;
+      if !prefix.file_type()?.is_dir() {
+        continue;
+      }
+      for imdir in fs::read_dir(prefix.path())? {
+        let imdir = imdir?.path();
+        if let Some((entry, records)) = self.migrate_image(&imdir)? {
+          manifests.retain(|m| m.digest != entry.digest);
+          manifests.push(entry);
+          migrated.extend(records);
+        }
+      }
+    }
+    self.write_index(manifests)?;
+    for record in migrated {
+      fs::remove_file(record)?;
+    }
+    info!(cache = ?self.root, "Cached images moved into the blob store");
+    Ok(())
+  }
+
+  /// The index entry of the image of the per-image directory, if any, along
+  /// with the files recording it there, to be removed once it's indexed.
+  fn migrate_image(
+    &self,
+    imdir: &Path,
+  ) -> Result<Option<(IndexEntry, Vec<PathBuf>)>> {
+    let mut cached = Cached {
+      image: String::new(),
+      tags: vec![],
+      digest: String::new(),
+      config: None,
+      created: None,
+      size: 0,
+      origin: Origin::Pulled,
+      fingerprint: None,
+    };
+    let mut records = Vec::new();
+    let (raw, media_type) = match (
+      fs::read(imdir.join("manifest.json")),
+      fs::read(imdir.join(INDEX_FILE)),
+    ) {
+      (Ok(raw), _) => {
+        let manifest: OciImageManifest = serde_json::from_slice(&raw)
+          .map_err(|err| Error::Unexpected(Box::from(err)))?;
+        for blob in manifest.layers.iter().chain([&manifest.config]) {
+          let hex = crate::bare_digest(blob.digest.clone());
+          self.take(&imdir.join(&hex), &hex)?;
+          cached.size += blob.size as u64;
+        }
+        let config = fs::read(self.blob_path(&manifest.config.digest));
+        cached.created = config.ok().and_then(|c| crate::created_at(&c));
+        cached.config = Some(manifest.config.digest);
+        let media_type = manifest
+          .media_type
+          .unwrap_or_else(|| crate::provenance::OCI_MANIFEST.to_string());
+        records.push(imdir.join("manifest.json"));
+        (raw, media_type)
+      }
+      (Err(_), Ok(raw)) => {
+        let index: ImageIndex = serde_json::from_slice(&raw)
+          .map_err(|err| Error::Unexpected(Box::from(err)))?;
+        let media_type = index
+          .media_type
+          .unwrap_or_else(|| index::OCI_INDEX.to_string());
+        records.push(imdir.join(INDEX_FILE));
+        (raw, media_type)
+      }
+      (Err(_), Err(_)) => return Ok(None),
+    };
+    let descriptor = self.put(&media_type, &raw)?;
+    if imdir.join("version").exists() {
+      records.push(imdir.join("version"));
+    }
+    // only the built images had their fingerprints recorded
+    if let Ok(fingerprint) = fs::read_to_string(imdir.join("fingerprint")) {
+      cached.fingerprint = Some(fingerprint);
+      cached.origin = Origin::Built;
+      records.push(imdir.join("fingerprint"));
+    }
+    cached.digest = descriptor.digest.clone();
+    debug!(image = ?descriptor.digest, "Cached image migrated");
+    Ok(Some((entry(descriptor, &cached), records)))
+  }
+
+  /// Moves the file into the store, as the blob of the digest. A duplicate of
+  /// a stored blob is dropped.
+  fn take(&self, file: &Path, hex: &str) -> Result<()> {
+    if !file.exists() {
+      return Ok(());
+    }
+    let path = self.blobs().join(hex);
+    if path.exists() {
+      fs::remove_file(file)?;
+    } else {
+      fs::rename(file, path)?;
+    }
+    Ok(())
+  }
+}
+
+/// The entry of an image within the index of the store, recording what the
+/// cache knows of it as annotations.
+pub(crate) fn entry(descriptor: OciDescriptor, cached: &Cached) -> IndexEntry {
+  let mut tags = cached.tags.clone();
+  tags.sort();
+  let mut annotations = BTreeMap::from([
+    (ORIGIN.to_string(), cached.origin.to_string()),
+    (SIZE.to_string(), cached.size.to_string()),
+  ]);
+  if !cached.image.is_empty() {
+    annotations.insert(NAME.to_string(), cached.image.clone());
+    annotations.insert(TAGS.to_string(), tags.join(","));
+  }
+  if let Some(tag) = tags.first() {
+    let reference = format!("{}:{}", cached.image, tag);
+    annotations.insert(REF_NAME.to_string(), reference);
+  }
+  if let Some(config) = &cached.config {
+    annotations.insert(CONFIG.to_string(), config.clone());
+  }
+  if let Some(created) = cached.created {
+    let created = chrono::DateTime::<chrono::Utc>::from(created)
+      .to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+    annotations.insert(CREATED.to_string(), created);
+  }
+  if let Some(fingerprint) = &cached.fingerprint {
+    annotations.insert(FINGERPRINT.to_string(), fingerprint.clone());
+  }
+  IndexEntry {
+    media_type: descriptor.media_type,
+    digest: descriptor.digest,
+    size: descriptor.size,
+    platform: None,
+    annotations: Some(annotations),
+  }
+}
+
+/// The entry, recording what the cache knows of its image anew.
+fn retagged(entry: &IndexEntry, cached: &Cached) -> IndexEntry {
+  let descriptor = OciDescriptor {
+    media_type: entry.media_type.clone(),
+    digest: entry.digest.clone(),
+    size: entry.size,
+    urls: None,
+    annotations: None,
+  };
+  self::entry(descriptor, cached)
+}
+
+/// What the cache knows of the image of the entry, as recorded by its
+/// annotations.
+pub(crate) fn cached(entry: &IndexEntry) -> Cached {
+  let empty = BTreeMap::new();
+  let annotations = entry.annotations.as_ref().unwrap_or(&empty);
+  let get = |key: &str| annotations.get(key).cloned();
+  let tags = get(TAGS).unwrap_or_default();
+  Cached {
+    image: get(NAME).unwrap_or_default(),
+    tags: tags
+      .split(',')
+      .filter(|t| !t.is_empty())
+      .map(|t| t.to_string())
+      .collect(),
+    digest: entry.digest.clone(),
+    config: get(CONFIG),
+    created: get(CREATED)
+      .and_then(|c| chrono::DateTime::parse_from_rfc3339(&c).ok())
+      .map(|c| c.into()),
+    size: get(SIZE).and_then(|s| s.parse().ok()).unwrap_or_default(),
+    origin: match get(ORIGIN).as_deref() {
+      Some("built") => Origin::Built,
+      _ => Origin::Pulled,
+    },
+    fingerprint: get(FINGERPRINT),
+  }
+}
+
+impl cache::HomeBased {
+  /// Removes the cached blobs no image refers to anymore, but for the ones
+  /// used within the last day, as builds running meanwhile may need them.
+  pub fn collect_garbage(&self) -> Result<Collected> {
+    Store::open(&self.config.workdir()?)?.collect_garbage()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::HashSet;
+  use std::fs::{self, File};
+  use std::path::Path;
+  use std::time::SystemTime;
+
+  use oci_distribution::manifest::{OciDescriptor, OciImageManifest};
+  use ocilot_core::build::ImageName;
+  use ocilot_core::oci::Cache;
+
+  use crate::cache::{Cached, HomeBased, Origin};
+  use crate::config::Config;
+  use crate::index::IndexEntry;
+  use crate::store::{self, Collected, Store, GRACE_PERIOD};
+
+  const BLOB: &str = "application/octet-stream";
+
+  fn store(name: &str) -> Store {
+    let dir = std::env::temp_dir().join(name);
+    let _ = fs::remove_dir_all(&dir);
+    Store::open(&dir).unwrap()
+  }
+
+  /// Backdates the file, past the grace period.
+  fn outdate(path: &Path) {
+    File::options()
+      .append(true)
+      .open(path)
+      .unwrap()
+      .set_modified(SystemTime::now() - GRACE_PERIOD * 2)
+      .unwrap();
+  }
+
+  #[test]
+  fn collected() {
+    let store = store("ocilot-store-collected");
+    let old = store.put(BLOB, b"old").unwrap();
+    let reused = store.put(BLOB, b"reused").unwrap();
+    let fresh = store.put(BLOB, b"fresh").unwrap();
+    let partial = store.blobs().join("0123.42-0.partial");
+    fs::write(&partial, b"partial").unwrap();
+    outdate(&store.blob_path(&old.digest));
+    outdate(&store.blob_path(&reused.digest));
+    store.put(BLOB, b"reused").unwrap();
+
+    let collected = store.collect_garbage().unwrap();
+
+    assert_eq!(collected, Collected { blobs: 1, bytes: 3 });
+    assert!(!store.blob_path(&old.digest).exists());
+    assert!(store.blob_path(&reused.digest).exists());
+    assert!(store.blob_path(&fresh.digest).exists());
+    assert!(partial.exists());
+  }
+
+  /// Lays out an image the way the caches predating the store did: its
+  /// blobs and records within its own directory.
+  fn old_image(workdir: &Path, fingerprint: &str) -> OciImageManifest {
+    let config = br#"{"created":"2023-01-02T03:04:05Z"}"#;
+    let layer = b"layer";
+    let descriptor = |data: &[u8]| OciDescriptor {
+      digest: crate::sha256_digest(data),
+      size: data.len() as i64,
+      ..Default::default()
+    };
+    let manifest = OciImageManifest {
+      schema_version: 2,
+      config: descriptor(config),
+      layers: vec![descriptor(layer)],
+      ..Default::default()
+    };
+    let raw = serde_json::to_vec(&manifest).unwrap();
+    let imdir = crate::cache::image_dir(
+      workdir,
+      &crate::bare_digest(crate::sha256_digest(&raw)),
+    );
+    fs::create_dir_all(&imdir).unwrap();
+    for (blob, data) in [
+      (&manifest.config, &config[..]),
+      (&manifest.layers[0], layer),
+    ] {
+      fs::write(imdir.join(crate::bare_digest(blob.digest.clone())), data)
+        .unwrap();
+    }
+    fs::write(imdir.join("manifest.json"), raw).unwrap();
+    fs::write(imdir.join("fingerprint"), fingerprint).unwrap();
+    fs::write(imdir.join("version"), "1").unwrap();
+    manifest
+  }
+
+  #[test]
+  fn migrated() {
+    let workdir = std::env::temp_dir().join("ocilot-store-migrated");
+    let _ = fs::remove_dir_all(&workdir);
+    let manifest = old_image(&workdir, "f1");
+    // the index can't be written, as if the migration was interrupted
+    let partial = workdir.join("index.json.partial");
+    fs::create_dir_all(&partial).unwrap();
+    assert!(Store::open(&workdir).is_err());
+    fs::remove_dir(partial).unwrap();
+
+    let store = Store::open(&workdir).unwrap();
+
+    let images = store.images().unwrap();
+    assert_eq!(images.len(), 1);
+    let cached = store::cached(&images[0]);
+    assert_eq!(cached.fingerprint.as_deref(), Some("f1"));
+    assert_eq!(cached.origin, Origin::Built);
+    assert_eq!(cached.config, Some(manifest.config.digest.clone()));
+    assert_eq!(cached.size, 5 + manifest.config.size as u64);
+    assert!(cached.created.is_some());
+    for digest in [&images[0].digest, &manifest.config.digest] {
+      assert!(store.blob_path(digest).exists(), "{}", digest);
+    }
+    assert!(store.blob_path(&manifest.layers[0].digest).exists());
+    let imdir =
+      crate::cache::image_dir(&workdir, &crate::bare_digest(cached.digest));
+    let left: Vec<_> = fs::read_dir(imdir).unwrap().collect();
+    assert!(left.is_empty(), "{:?}", left);
+  }
+
+  /// The entry of a built image of the name and tags, the manifest and config
+  /// of which are stored. Its config, and so its digest, tells its build.
+  fn built(
+    store: &Store,
+    image: &str,
+    tags: &[&str],
+    build: &str,
+  ) -> IndexEntry {
+    let config = store.put(BLOB, build.as_bytes()).unwrap();
+    let manifest = OciImageManifest {
+      schema_version: 2,
+      config: config.clone(),
+      ..Default::default()
+    };
+    let raw = serde_json::to_vec(&manifest).unwrap();
+    let descriptor = store.put(crate::provenance::OCI_MANIFEST, &raw).unwrap();
+    let cached = Cached {
+      image: image.to_string(),
+      tags: tags.iter().map(|t| t.to_string()).collect(),
+      digest: descriptor.digest.clone(),
+      config: Some(config.digest),
+      created: None,
+      size: 0,
+      origin: Origin::Built,
+      fingerprint: Some("f1".to_string()),
+    };
+    store::entry(descriptor, &cached)
+  }
+
+  #[test]
+  fn tagged() {
+    let store = store("ocilot-store-tagged");
+    store.add(built(&store, "app", &["v1"], "{}")).unwrap();
+    store.add(built(&store, "app", &["v2"], "{}")).unwrap();
+    store.add(built(&store, "other", &["v1"], "{}")).unwrap();
+    let cache = HomeBased {
+      config: Box::new(Config {
+        workdir: store.root().to_path_buf(),
+      }),
+    };
+    let names = |image: &str, tag: &str| -> Vec<ImageName> {
+      let found = cache.find(image, Some(tag)).unwrap();
+      found.iter().map(|i| i.name()).collect()
+    };
+    let name = |image: &str, tags: &[&str]| ImageName {
+      image: image.to_string(),
+      tags: HashSet::from_iter(tags.iter().map(|t| t.to_string())),
+    };
+
+    assert_eq!(store.images().unwrap().len(), 2);
+    assert_eq!(names("app", "v1"), vec![name("app", &["v1", "v2"])]);
+    assert_eq!(names("app", "v2"), vec![name("app", &["v1", "v2"])]);
+    assert_eq!(names("other", "v1"), vec![name("other", &["v1"])]);
+  }
+
+  #[test]
+  fn rebuilt() {
+    let store = store("ocilot-store-rebuilt");
+    let first = built(&store, "app", &["latest", "v1"], r#"{"n":1}"#);
+    let second = built(&store, "app", &["latest"], r#"{"n":2}"#);
+    store.add(first.clone()).unwrap();
+    store.add(second.clone()).unwrap();
+    let cache = HomeBased {
+      config: Box::new(Config {
+        workdir: store.root().to_path_buf(),
+      }),
+    };
+    let digests = |tag: &str| -> Vec<String> {
+      let found = cache.find("app", Some(tag)).unwrap();
+      found.iter().map(|i| i.digest()).collect()
+    };
+
+    assert_eq!(
+      digests("latest"),
+      vec![crate::bare_digest(second.digest.clone())]
+    );
+    assert_eq!(digests("v1"), vec![crate::bare_digest(first.digest)]);
+  }
+
+  #[test]
+  fn superseded() {
+    let store = store("ocilot-store-superseded");
+    let first = built(&store, "app", &["latest"], r#"{"n":1}"#);
+    let second = built(&store, "app", &["latest"], r#"{"n":2}"#);
+    store.add(first.clone()).unwrap();
+    store.add(second.clone()).unwrap();
+    for entry in fs::read_dir(store.blobs()).unwrap() {
+      outdate(&entry.unwrap().path());
+    }
+
+    let collected = store.collect_garbage().unwrap();
+
+    let images = store.images().unwrap();
+    assert_eq!(images.len(), 1);
+    assert_eq!(images[0].digest, second.digest);
+    assert_eq!(collected.blobs, 2);
+    let config = store::cached(&first).config.unwrap();
+    for digest in [&first.digest, &config] {
+      assert!(!store.blob_path(digest).exists(), "{}", digest);
+    }
+    assert!(store.blob_path(&second.digest).exists());
+  }
+}
@@ -0,0 +1,75 @@
+use std::fmt::{Debug, Formatter};
+use std::future::Future;
+use std::sync::Arc;
+use std::{panic, thread};
+
+use ocilot_core::error::Result;
+use tokio::runtime::{Handle, Runtime};
+
+/// How many blobs are transferred at once, when not told otherwise.
+pub const DEFAULT_CONCURRENCY: usize = 4;
+
+/// Runs the registry calls of an invocation on a single runtime, shared by
+/// its clones, transferring at most `concurrency` blobs at once.
+///
+/// The calls block the caller. Ones made from within a Tokio runtime, like
+/// the one of an embedder, are run by a helper thread, as blocking on the
+/// runtime would panic. The calling worker still waits for them, stalling
+/// the other tasks it runs, so async embedders should rather await the async
+/// API of [`Rest`](crate::registry::Rest), or call the builds from
+/// `tokio::task::spawn_blocking`.
+#[derive(Clone)]
+pub struct Transfers {
+  runtime: Arc<Shared>,
+  pub concurrency: usize,
+}
+
+/// The runtime, shut down in the background once the last clone is dropped,
+/// so it may be dropped from within an async context.
+struct Shared(Option<Runtime>);
+
+impl Transfers {
+  pub fn new(concurrency: usize) -> Result<Transfers> {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+      .enable_all()
+      .thread_name("ocilot-transfer")
+      .build()?;
+    Ok(Transfers {
+      runtime: Arc::new(Shared(Some(runtime))),
+      concurrency: concurrency.max(1),
+    })
+  }
+
+  pub(crate) fn block_on<F>(&self, future: F) -> F::Output
+  where
+    F: Future + Send,
+    F::Output: Send,
+  {
+    let runtime = self.runtime.0.as_ref().expect("runtime is shut down");
+    if Handle::try_current().is_err() {
+      return runtime.block_on(future);
+    }
+    thread::scope(|scope| {
+      scope
+        .spawn(|| runtime.block_on(future))
+        .join()
+        .unwrap_or_else(|cause| panic::resume_unwind(cause))
+    })
+  }
+}
+
+impl Debug for Transfers {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    f.debug_struct("Transfers")
+      .field("concurrency", &self.concurrency)
+      .finish_non_exhaustive()
+  }
+}
+
+impl Drop for Shared {
+  fn drop(&mut self) {
+    if let Some(runtime) = self.0.take() {
+      runtime.shutdown_background();
+    }
+  }
+}
@@ -5,11 +5,17 @@ use std::path::PathBuf;
 use clap;
 use clap::Parser;
 use ocilot_core as core;
+use ocilot_oci::{config, registry, transfer};
 use tracing::Level;
 
 use crate::cli::error::{Cause, Error, Result};
+use crate::cli::fingerprint;
+use crate::cli::gc;
 use crate::cli::list;
+use crate::cli::policy;
 use crate::cli::publish;
+use crate::cli::schema;
+use crate::cli::sign;
 use crate::cli::verbosity::Verbosity;
 use crate::cli::{build, logging};
 
@@ -33,6 +39,23 @@ pub(crate) struct Args {
   /// example on Linux that's `$XDG_CACHE_HOME/ocilot` or $HOME/.cache/ocilot`.
   #[clap(short = 'c', long = "cache-dir", global = true, required = false)]
   cachedir: Option<PathBuf>,
+
+  /// A trust policy file, enforced on every base image pulled, and every image
+  /// signed. It lists the allowed registries and repositories, and may
+  /// require digest-pinned bases, forbid the "latest" tag, and require
+  /// signatures per scope.
+  #[clap(long, global = true, env = "OCILOT_POLICY")]
+  policy: Option<PathBuf>,
+
+  /// How many blobs are transferred with the registries at once, like the
+  /// layers of the base images.
+  #[clap(
+    long,
+    global = true,
+    env = "OCILOT_TRANSFERS",
+    default_value_t = transfer::DEFAULT_CONCURRENCY
+  )]
+  transfers: usize,
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug, clap::ArgEnum)]
@@ -45,10 +68,24 @@ enum Format {
 enum Commands {
   /// Builds a OCI image by stacking artifacts on top of base image.
   Build(build::Build),
-  /// Publish a build image to remote registry.
+  /// Publish a build image to remote registry. Not yet implemented, so the
+  /// provenance, bill of materials and signatures of a build stay in the
+  /// cache.
   Publish(publish::Publish),
-  /// List locally built images.
+  /// Signs an image of a remote registry with a local key, the way cosign
+  /// does, and pushes the signature along it.
+  Sign(sign::Sign),
+  /// Lists the images of the local cache, the ones built and the pulled
+  /// base images.
   List(list::List),
+  /// Removes the blobs of the local cache no cached image refers to anymore,
+  /// like the ones of images superseded by a build or pull of their tags.
+  Gc(gc::Gc),
+  /// Prints the fingerprint of a build, without building it. The same
+  /// fingerprint means the same resulting image.
+  Fingerprint(fingerprint::Fingerprint),
+  /// Prints the JSON schema of the ocilot.toml build file.
+  Schema(schema::Schema),
 }
 
 impl Args {
@@ -64,6 +101,27 @@ impl Args {
   }
 }
 
+impl Args {
+  /// The trust policy of the given file, or the default one, allowing
+  /// anything.
+  pub fn trust_policy(&self) -> Result<core::policy::Policy> {
+    match &self.policy {
+      Some(path) => policy::load(path),
+      None => Ok(Default::default()),
+    }
+  }
+
+  /// The remote registries, reached on a runtime shared by the invocation.
+  pub fn registry(&self) -> Result<registry::Rest> {
+    Ok(registry::Rest {
+      config: Box::new(config::Config {
+        workdir: self.ocilot_dir()?,
+      }) as Box<dyn core::oci::Config>,
+      transfers: transfer::Transfers::new(self.transfers)?,
+    })
+  }
+}
+
 pub(crate) trait Executable {
   fn execute(&self, args: &Args) -> Result<()>;
 }
@@ -135,7 +193,11 @@ fn try_execute_with_args(ctx: ExecutionContext, args: Args) -> Result<()> {
     match &args.command {
       Commands::Build(build) => build.execute(&args),
       Commands::Publish(publish) => publish.execute(&args),
+      Commands::Sign(sign) => sign.execute(&args),
       Commands::List(list) => list.execute(&args),
+      Commands::Gc(gc) => gc.execute(&args),
+      Commands::Fingerprint(fp) => fp.execute(&args),
+      Commands::Schema(schema) => schema.execute(&args),
     }
   })
 }
@@ -198,6 +260,21 @@ mod tests {
     assert!(result.is_err());
   }
 
+  #[test]
+  fn sign_without_key() {
+    let tec = TestExecutionContext::new(vec![
+      "ocilot",
+      "sign",
+      "quay.io/cardil/ocilot-sources:latest",
+      "--key",
+      "no-such-key.pem",
+    ]);
+
+    let result = args::try_execute(tec.ctx());
+
+    assert!(result.is_err());
+  }
+
   #[test]
   fn build() {
     let tec = TestExecutionContext::new(vec![
@@ -1,28 +1,65 @@
 use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 
 use clap::Args;
 use ocilot_core as core;
 use ocilot_core::build::Built;
 use ocilot_fs::{file, glob};
-use ocilot_oci::{cache, config, registry};
-use regex::RegexBuilder;
+use ocilot_oci::{cache, config, signing};
+use regex::{Regex, RegexBuilder};
 use tracing::instrument;
-use tracing::{debug, info, trace, warn};
+use tracing::{debug, error, info, trace, warn};
 
-use crate::cli::{args, error};
+use crate::cli::image_config::{self, ImageConfig};
+use crate::cli::project::{self, Project, Target};
+use crate::cli::{args, error, plan, progress};
+use schemars::JsonSchema;
+use serde::Deserialize;
 
-#[derive(Debug, Args)]
+#[derive(Debug, Default, Args)]
 pub struct Build {
+  /// A target of the build file to build. All of its targets are built, if
+  /// not given.
+  target: Option<String>,
+  /// A build file describing the images to build. It's read when either
+  /// --base or --image is missing. The options given override its values,
+  /// and --base or --image only when a single target gets built. The targets
+  /// a target given is built upon are built as the file describes them.
+  #[clap(short = 'f', long)]
+  file: Option<PathBuf>,
   /// A base image to build upon. Short image name will resolve to docker.io
-  #[clap(short = 'b', long, required = true)]
-  base: String,
+  /// Another target of the build file is referred to as "target:<name>", and
+  /// an image built before, found in the local cache, as "local:<image>[:tag]",
+  /// or "local:<image>@<digest>".
+  #[clap(short = 'b', long)]
+  base: Option<String>,
   /// Image name to build, without tags. Short image name will resolve to docker.io
-  #[clap(short = 'i', long, required = true)]
-  image: String,
+  #[clap(short = 'i', long)]
+  image: Option<String>,
   /// Artifact(s) to add on top of base image. Repeat the option to add
   /// multiple artifacts. Artifact spec needs to be in form:
   ///
-  /// "[arch:]<file-or-glob-on-host>[:file-or-dir-on-image]".
+  /// "[arch:]<path-or-glob-on-host>[:file-or-dir-on-image][,option=value]".
+  ///
+  /// Directories are copied recursively, and the files matched by a glob keep
+  /// their path relative to the glob's literal prefix, like with `COPY`.
+  ///
+  /// Options are: "mode" with octal permission bits (host ones are kept by
+  /// default), "owner" with a user, and optionally a group, given by numeric
+  /// ids or names from the base image (root by default), "symlinks" with
+  /// "keep" to add host symlinks as such, instead of following them, "layer"
+  /// with a name of the layer to share with other artifacts, and "exclude"
+  /// with a glob of paths to leave out, relative to the walked directory.
+  /// Repeat "exclude" to leave out more paths. Commas within braces belong to
+  /// the glob, as in "exclude={target,vendor}/**".
+  ///
+  /// Paths listed in .ocilotignore files are left out of directories and
+  /// globs, like with .gitignore.
+  ///
+  /// A symlink is created within the image by the
+  /// "[arch:]<link-on-image> -> <target>" form. Files sharing an inode on host
+  /// are stored once, as hard links.
   ///
   /// Example forms:
   ///
@@ -34,15 +71,26 @@ pub struct Build {
   ///
   ///  -a target/*.jar:/usr/lib/app
   ///
+  ///  -a static:/srv/www
+  ///
+  ///  -a "static/**/*.{html,css}:/srv/www"
+  ///
   ///  -a amd64:target/acme-linux-amd64:/usr/bin/acme
   ///
   ///  -a arm64:target/acme-linux-arm64:/usr/bin/acme
-  #[clap(
-    short = 'a',
-    long = "artifact",
-    multiple_occurrences = true,
-    required = true
-  )]
+  ///
+  ///  -a target/acme:/usr/bin/acme,mode=0755,owner=65532:65532
+  ///
+  ///  -a acme.toml:/etc/acme.toml,mode=0644,owner=root
+  ///
+  ///  -a "lib/:/usr/lib/acme/,symlinks=keep"
+  ///
+  ///  -a "target/deps/*.jar:/opt/app/lib/,layer=deps"
+  ///
+  ///  -a "src/**/*.rs:/usr/src/acme/,exclude=**/tests,exclude=**/benches"
+  ///
+  ///  -a "/usr/bin/acme -> /opt/acme/bin/acme"
+  #[clap(short = 'a', long = "artifact", multiple_occurrences = true)]
   artifacts: Vec<String>,
   /// Architectures to build the image for. Repeat the option to add
   /// multiple values. If not given the architectures from base image will
@@ -53,32 +101,167 @@ pub struct Build {
   /// values. If not given the no tags will be used.
   #[clap(short = 't', long = "tag", multiple_occurrences = true)]
   tags: Vec<String>,
+  /// A fixed creation time of the image and its files, as Unix timestamp.
+  /// Makes the build reproducible. Defaults to SOURCE_DATE_EPOCH, if set.
+  #[clap(long, env = "SOURCE_DATE_EPOCH")]
+  timestamp: Option<u64>,
+  /// How the artifacts are split into layers. Artifacts naming their layer
+  /// with the "layer" option are grouped regardless. Layers are stacked in
+  /// the order artifacts are given, so put rarely changing ones first.
+  #[clap(arg_enum, long)]
+  layering: Option<Layering>,
+  /// Leaves out the paths listed in .gitignore files too, when walking
+  /// directories and globs.
+  #[clap(long)]
+  gitignore: bool,
+  /// Records a bill of materials of the image, in the given format, next to
+  /// it in the cache. It lists the added files, the base image, and the Rust
+  /// crates of binaries built with `cargo auditable`. It isn't pushed yet, as
+  /// publishing isn't implemented.
+  #[clap(arg_enum, long)]
+  sbom: Option<Sbom>,
+  /// Signs the images with the private key file, the way cosign does. The
+  /// signature is only kept next to the image in the cache, as publishing
+  /// isn't implemented yet; use `ocilot sign` on a pushed image instead.
+  /// A PEM encoded PKCS#8 key, either ECDSA P-256 or ed25519, is expected.
+  #[clap(long, value_name = "KEY")]
+  sign: Option<PathBuf>,
+  /// Requires the base images to carry a cosign signature, pushed under the
+  /// "sha256-<digest>.sig" tag, verifying against the public key file. The
+  /// base is then fetched by its verified digest. Local bases are trusted.
+  #[clap(long, value_name = "KEY", env = "OCILOT_BASE_KEY")]
+  base_key: Option<PathBuf>,
+  /// Builds the image twice, and fails if the digests differ. Needs a
+  /// timestamp, given by --timestamp or SOURCE_DATE_EPOCH.
+  #[clap(long)]
+  verify_reproducible: bool,
+  /// Prints the files the image would contain, without building it or
+  /// contacting a registry.
+  #[clap(long, conflicts_with = "verify-reproducible")]
+  dry_run: bool,
+  /// How many targets are built at once. Defaults to the number of CPUs.
+  /// Base images shared by the targets are fetched once, and a failed
+  /// target doesn't stop the others.
+  #[clap(short = 'j', long)]
+  jobs: Option<usize>,
+  #[clap(flatten)]
+  config: ImageConfig,
 }
 
 impl args::Executable for Build {
   fn execute(&self, args: &args::Args) -> error::Result<()> {
-    let cmd = new_command(args)?;
-    debug!("Building...");
-    let build = self.to_core();
-    let result = cmd.execute(&build);
-    result
-      .map(|built| {
-        match built {
-          Built::Cached(ii) => info!(image = ?ii.digest, "Image already built"),
-          Built::Real(ii) => info!(image = ?ii.digest, "Build successful"),
-        };
-      })
-      .map_err(|err| error::Error::from(err))
+    let mut cmd = new_command(args)?;
+    cmd.observer = Some(Box::new(progress::Progress {}));
+    if let Some(key) = &self.sign {
+      cmd.oci.signer = Some(Box::new(signing::KeySigner::from_file(key)?));
+    }
+    if let Some(key) = &self.base_key {
+      let verifier = signing::KeyVerifier::from_file(key)?;
+      cmd.oci.verifier = Some(Box::new(verifier));
+    }
+    let targets = self.targets()?;
+    if self.dry_run {
+      let cwd = std::env::current_dir().map_err(core::error::Error::from)?;
+      for (name, build) in &targets {
+        println!("{}", plan::render(name, &cmd.plan(build)?, &cwd));
+      }
+      return Ok(());
+    }
+    if self.verify_reproducible {
+      if let Some((name, _)) =
+        targets.iter().find(|(_, b)| b.timestamp.is_none())
+      {
+        return Err(
+          core::error::Error::invalid_input(&format!(
+            "target {} needs a timestamp to be verified reproducible, set \
+             --timestamp or SOURCE_DATE_EPOCH",
+            name
+          ))
+          .into(),
+        );
+      }
+      for (name, build) in targets {
+        debug!(name = ?name, "Building...");
+        let ii = cmd.verify_reproducible(&build)?;
+        info!(name = ?name, image = ?ii.digest, "Build is reproducible");
+      }
+      return Ok(());
+    }
+    let (names, builds): (Vec<String>, Vec<core::build::Build>) =
+      targets.into_iter().unzip();
+    let jobs = self.jobs.unwrap_or_else(default_jobs);
+    let mut results = cmd.execute_all(&builds, jobs);
+    if results.len() == 1 {
+      report(&names[0], results.remove(0)?);
+      return Ok(());
+    }
+    let width = names.iter().map(String::len).max().unwrap_or_default();
+    let (mut built, mut cached, mut failed) = (0, 0, 0);
+    for (name, result) in names.iter().zip(results) {
+      let (status, detail) = match result {
+        Ok(Built::Cached(ii)) => {
+          cached += 1;
+          ("cached", ii.digest)
+        }
+        Ok(Built::Real(ii)) => {
+          built += 1;
+          ("built", ii.digest)
+        }
+        Err(err) => {
+          failed += 1;
+          error!(name = ?name, error = %err, "Build failed");
+          ("failed", err.to_string())
+        }
+      };
+      println!("{:6}  {:width$}  {}", status, name, detail, width = width);
+    }
+    println!(
+      "{} targets: {} built, {} cached, {} failed",
+      names.len(),
+      built,
+      cached,
+      failed
+    );
+    if failed > 0 {
+      return Err(
+        core::error::Error::BatchFailed {
+          failed,
+          total: names.len(),
+        }
+        .into(),
+      );
+    }
+    Ok(())
   }
 }
 
-fn new_command(args: &args::Args) -> error::Result<core::build::Command> {
+fn report(name: &str, built: Built) {
+  match built {
+    Built::Cached(ii) => info!(
+      name = ?name,
+      image = ?ii.digest,
+      fingerprint = ?ii.fingerprint,
+      "Image already built"
+    ),
+    Built::Real(ii) => info!(
+      name = ?name,
+      image = ?ii.digest,
+      fingerprint = ?ii.fingerprint,
+      "Build successful"
+    ),
+  };
+}
+
+/// As many jobs as the available CPUs, or one, if they're unknown.
+fn default_jobs() -> usize {
+  std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+pub(crate) fn new_command(
+  args: &args::Args,
+) -> error::Result<core::build::Command> {
   let workdir = args.ocilot_dir()?;
-  let registry = Box::new(registry::Rest {
-    config: Box::new(config::Config {
-      workdir: workdir.clone(),
-    }) as Box<dyn core::oci::Config>,
-  });
+  let registry = Box::new(args.registry()?);
   let cache = Box::new(cache::HomeBased {
     config: Box::new(config::Config { workdir }) as Box<dyn core::oci::Config>,
   });
@@ -86,48 +269,258 @@ fn new_command(args: &args::Args) -> error::Result<core::build::Command> {
   let files = Box::new(file::LocalFileSystem {});
   Ok(core::build::Command {
     fs: core::build::FileSystem { resolver, files },
-    oci: core::build::Oci { registry, cache },
+    oci: core::build::Oci {
+      registry,
+      cache,
+      signer: None,
+      verifier: None,
+      policy: args.trust_policy()?,
+    },
+    observer: None,
   })
 }
 
 impl Build {
+  /// Resolves the builds to perform, by their target names. The build file is
+  /// read, unless both the base and the image are given.
+  pub(crate) fn targets(
+    &self,
+  ) -> error::Result<Vec<(String, core::build::Build)>> {
+    let standalone = self.base.is_some() && self.image.is_some();
+    let project = match &self.file {
+      Some(path) => Some(Project::read(path)?.ok_or_else(|| {
+        core::error::Error::invalid_input(&format!(
+          "build file not found: {}",
+          path.display()
+        ))
+      })?),
+      None if standalone => None,
+      None => Project::read(Path::new(project::FILENAME))?,
+    };
+    match project {
+      Some(project) => {
+        let selected = project.select(self.target.as_deref())?;
+        if selected.len() > 1 {
+          if let Some(flag) = self.overriding_flag() {
+            return Err(
+              core::error::Error::invalid_input(&format!(
+                "{} can't override {} targets at once, pick a target \
+                 building alone",
+                flag,
+                selected.len()
+              ))
+              .into(),
+            );
+          }
+        }
+        // the targets the one given is built upon take no options
+        let plain = Build::default();
+        selected
+          .into_iter()
+          .map(|(name, target)| {
+            let given = self.target.as_ref().is_none_or(|t| t == name);
+            let options = if given { self } else { &plain };
+            let mut build = options.to_core(target)?;
+            build.base = project.resolve_base(&build.base)?;
+            Ok((name.clone(), build))
+          })
+          .collect()
+      }
+      None => match &self.target {
+        Some(name) => Err(
+          core::error::Error::invalid_input(&format!(
+            "no build file to pick the target {:?} from",
+            name
+          ))
+          .into(),
+        ),
+        None => {
+          let build = self.to_core(&Target::default())?;
+          if build.base.starts_with(project::TARGET_BASE) {
+            return Err(
+              core::error::Error::invalid_input(&format!(
+                "no build file to pick the base {:?} from",
+                build.base
+              ))
+              .into(),
+            );
+          }
+          Ok(vec![(build.image.image.clone(), build)])
+        }
+      },
+    }
+  }
+
+  /// The option overriding the base or the image, which can't be applied to
+  /// more than one target.
+  fn overriding_flag(&self) -> Option<&'static str> {
+    match (&self.base, &self.image) {
+      (Some(_), _) => Some("--base"),
+      (None, Some(_)) => Some("--image"),
+      (None, None) => None,
+    }
+  }
+
+  /// Converts to a core build, with options given overriding the values of
+  /// the build file target.
   #[instrument]
-  pub fn to_core(&self) -> core::build::Build {
-    let base = self.base.to_owned();
-    let image = self.image.to_owned();
-    let tags = self.tags.iter().cloned().collect();
-    let arch = self
-      .arch
+  fn to_core(&self, target: &Target) -> error::Result<core::build::Build> {
+    let base = self
+      .base
+      .clone()
+      .or_else(|| target.base.clone())
+      .ok_or_else(|| missing("base image", "--base"))?;
+    let image = self
+      .image
+      .clone()
+      .or_else(|| target.image.clone())
+      .ok_or_else(|| missing("image name", "--image"))?;
+    let tags = overriding(&self.tags, &target.tags)
       .iter()
-      .map(|repr| arch_from_string(repr))
-      .map(|res| res.unwrap())
+      .cloned()
       .collect();
-    let artifacts = self
-      .artifacts
+    let arch = overriding(&self.arch, &target.arch)
       .iter()
-      .map(|repr| artifact_from_string(repr))
-      .map(|res| res.unwrap())
-      .collect();
+      .map(arch_from_string)
+      .collect::<Result<_, Error>>()
+      .map_err(invalid)?;
+    let specs = if self.artifacts.is_empty() {
+      target
+        .artifacts
+        .iter()
+        .map(spec_from_project)
+        .collect::<Result<Vec<_>, Error>>()
+    } else {
+      self
+        .artifacts
+        .iter()
+        .map(|repr| spec_from_string(repr))
+        .collect::<Result<Vec<_>, Error>>()
+    }
+    .map_err(invalid)?;
+    if specs.is_empty() {
+      return Err(missing("artifacts", "--artifact"));
+    }
+    let gitignore = self.gitignore || target.gitignore.unwrap_or_default();
+    let mut artifacts = Vec::new();
+    let mut symlinks = Vec::new();
+    for spec in specs {
+      match spec {
+        Spec::Artifact(artifact) => artifacts.push(core::Artifact {
+          gitignore,
+          ..artifact
+        }),
+        Spec::Symlink(link) => symlinks.push(link),
+      }
+    }
+    let timestamp = self
+      .timestamp
+      .or(target.timestamp)
+      .map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs));
+    let config = image_config::layered(
+      target.config.to_core().map_err(invalid)?,
+      self.config.to_core().map_err(invalid)?,
+    );
     trace!("inside to_core, within span");
-    return core::build::Build {
+    Ok(core::build::Build {
       base,
       image: core::build::ImageName { image, tags },
       arch,
       artifacts,
-    };
+      timestamp,
+      config,
+      symlinks,
+      layering: self.layering.or(target.layering).unwrap_or_default().into(),
+      sbom: self.sbom.or(target.sbom).map(Into::into),
+    })
+  }
+}
+
+/// How the artifacts are split into layers.
+#[derive(
+  Copy,
+  Clone,
+  PartialEq,
+  Eq,
+  Debug,
+  Default,
+  clap::ArgEnum,
+  Deserialize,
+  JsonSchema,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Layering {
+  /// All artifacts share a single layer.
+  #[default]
+  Single,
+  /// Each artifact gets a layer of its own.
+  PerArtifact,
+}
+
+impl From<Layering> for core::build::Layering {
+  fn from(layering: Layering) -> Self {
+    match layering {
+      Layering::Single => core::build::Layering::Single,
+      Layering::PerArtifact => core::build::Layering::PerArtifact,
+    }
   }
 }
 
-fn invalid_format(repr: &String) -> Error {
+/// Formats of the bill of materials.
+#[derive(
+  Copy, Clone, PartialEq, Eq, Debug, clap::ArgEnum, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Sbom {
+  /// SPDX 2.3, in JSON.
+  Spdx,
+  /// CycloneDX 1.5, in JSON.
+  #[clap(name = "cyclonedx")]
+  #[serde(rename = "cyclonedx")]
+  CycloneDx,
+}
+
+impl From<Sbom> for core::build::Sbom {
+  fn from(sbom: Sbom) -> Self {
+    match sbom {
+      Sbom::Spdx => core::build::Sbom::Spdx,
+      Sbom::CycloneDx => core::build::Sbom::CycloneDx,
+    }
+  }
+}
+
+/// The values given as options, or the build file ones, if none were given.
+fn overriding<'a>(given: &'a [String], file: &'a [String]) -> &'a [String] {
+  if given.is_empty() {
+    file
+  } else {
+    given
+  }
+}
+
+fn missing(what: &str, option: &str) -> error::Error {
+  core::error::Error::invalid_input(&format!(
+    "no {} given, use {} or set it in the build file",
+    what, option
+  ))
+  .into()
+}
+
+fn invalid(err: Error) -> error::Error {
+  core::error::Error::invalid_input_from(Box::new(err)).into()
+}
+
+fn invalid_format(repr: &str) -> Error {
   Error::new(ErrorKind::InvalidInput, format!("bad format: {:?}", repr))
 }
 
-fn artifact_from_string(repr: &String) -> Result<core::Artifact, Error> {
+fn artifact_from_string(repr: &str) -> Result<core::Artifact, Error> {
+  let (spec, options) = split_options(repr);
   // Ref.: https://regex101.com/r/q2qVXt/1
   let raw_re =
     r"^(?:(?P<arch>[^\n:]+):)?(?P<from>[^\n:]+)(?::(?P<to>[^\n:]+))?$";
   let re = RegexBuilder::new(raw_re).swap_greed(true).build().unwrap();
-  match re.captures(repr) {
+  let mut artifact = match re.captures(spec) {
     None => Err(invalid_format(repr)),
     Some(cap) => cap
       .name("from")
@@ -143,8 +536,152 @@ fn artifact_from_string(repr: &String) -> Result<core::Artifact, Error> {
           Some(m) => Some(arch_from_string(&m.as_str().to_string())),
         }
         .transpose()
-        .map(|arch| core::Artifact { arch, from, to })
+        .map(|arch| core::Artifact {
+          arch,
+          from,
+          to,
+          mode: None,
+          owner: None,
+          keep_symlinks: false,
+          exclude: vec![],
+          gitignore: false,
+          layer: None,
+        })
       }),
+  }?;
+  for (key, value) in options {
+    match key {
+      "mode" => {
+        artifact.mode = Some(mode_from_string(value, repr)?);
+      }
+      "owner" => artifact.owner = Some(owner_from_string(value, repr)?),
+      "symlinks" => {
+        artifact.keep_symlinks = match value {
+          "keep" => true,
+          "follow" => false,
+          _ => return Err(invalid_format(repr)),
+        }
+      }
+      "layer" if !value.is_empty() => artifact.layer = Some(value.to_string()),
+      "exclude" if !value.is_empty() => {
+        artifact.exclude.push(value.to_string())
+      }
+      _ => return Err(invalid_format(repr)),
+    }
+  }
+  Ok(artifact)
+}
+
+/// Splits the trailing ",option=value" pairs from the artifact spec. Values
+/// keep the commas within braces, like the alternatives of a glob.
+fn split_options(repr: &str) -> (&str, Vec<(&str, &str)>) {
+  let option = r"(?:mode|owner|symlinks|layer|exclude)=(?:[^,{}]|\{[^{}]*\})*";
+  let re = Regex::new(&format!(r"(?:,{})+$", option)).unwrap();
+  match re.find(repr) {
+    None => (repr, Vec::new()),
+    Some(m) => {
+      let options = Regex::new(&format!(",({})", option))
+        .unwrap()
+        .captures_iter(m.as_str())
+        .filter_map(|cap| cap.get(1)?.as_str().split_once('='))
+        .collect();
+      (&repr[..m.start()], options)
+    }
+  }
+}
+
+/// An artifact spec: files from host, or a symlink to create in the image.
+#[derive(Debug)]
+enum Spec {
+  Artifact(core::Artifact),
+  Symlink(core::Symlink),
+}
+
+fn spec_from_string(repr: &str) -> Result<Spec, Error> {
+  // Ref.: "[arch:]/usr/bin/acme -> /opt/acme/bin/acme"
+  let re = Regex::new(
+    r"^(?:(?P<arch>[^\s:/]+):)?(?P<path>[^\s:]+)\s*->\s*(?P<target>\S+)$",
+  )
+  .unwrap();
+  match re.captures(repr) {
+    Some(cap) => Ok(Spec::Symlink(core::Symlink {
+      arch: cap
+        .name("arch")
+        .map(|m| arch_from_string(&m.as_str().to_string()))
+        .transpose()?,
+      path: cap["path"].to_string(),
+      target: cap["target"].to_string(),
+    })),
+    None if repr.contains("->") => Err(invalid_format(repr)),
+    None => artifact_from_string(repr).map(Spec::Artifact),
+  }
+}
+
+fn spec_from_project(artifact: &project::Artifact) -> Result<Spec, Error> {
+  match artifact {
+    project::Artifact::Spec(repr) => spec_from_string(repr),
+    project::Artifact::Parts(project::Parts {
+      from,
+      to,
+      arch,
+      mode,
+      owner,
+      keep_symlinks,
+      exclude,
+      layer,
+    }) => Ok(Spec::Artifact(core::Artifact {
+      arch: arch.as_ref().map(arch_from_string).transpose()?,
+      from: from.clone(),
+      to: to.clone(),
+      mode: mode
+        .as_deref()
+        .map(|value| mode_from_string(value, from))
+        .transpose()?,
+      owner: owner
+        .as_deref()
+        .map(|value| owner_from_string(value, from))
+        .transpose()?,
+      keep_symlinks: keep_symlinks.unwrap_or_default(),
+      exclude: exclude.clone(),
+      gitignore: false,
+      layer: layer.clone(),
+    })),
+    project::Artifact::Symlink(project::Symlink { link, target, arch }) => {
+      Ok(Spec::Symlink(core::Symlink {
+        arch: arch.as_ref().map(arch_from_string).transpose()?,
+        path: link.clone(),
+        target: target.clone(),
+      }))
+    }
+  }
+}
+
+fn mode_from_string(value: &str, repr: &str) -> Result<u32, Error> {
+  u32::from_str_radix(value, 8)
+    .ok()
+    .filter(|m| *m <= 0o7777)
+    .ok_or_else(|| invalid_format(repr))
+}
+
+fn owner_from_string(value: &str, repr: &str) -> Result<core::Owner, Error> {
+  let id = |part: &str| {
+    if part.is_empty() {
+      return Err(invalid_format(repr));
+    }
+    Ok(match part.parse::<u32>() {
+      Ok(num) => core::Id::Numeric(num),
+      Err(_) => core::Id::Name(part.to_string()),
+    })
+  };
+  match value.split_once(':') {
+    None => Ok(core::Owner {
+      user: id(value)?,
+      group: None,
+    }),
+    Some((user, group)) => Ok(core::Owner {
+      user: id(user)?,
+      group: Some(id(group)?),
+    }),
   }
 }
 
@@ -163,10 +700,13 @@ fn arch_from_string(repr: &String) -> Result<core::Arch, Error> {
 
 #[cfg(test)]
 mod tests {
-  use std::collections::HashSet;
+  use std::collections::{BTreeMap, HashSet};
   use std::io::{Error, ErrorKind};
+  use std::time::{Duration, SystemTime};
 
+  use clap::Parser;
   use ocilot_core as core;
+  use ocilot_core::oci::Change;
 
   use crate::cli::build as cli;
 
@@ -219,13 +759,135 @@ mod tests {
     );
   }
 
+  #[test]
+  fn artifact_from_string_with_options() {
+    let cases = vec![
+      (
+        "target/acme:/usr/bin/acme,mode=0755,owner=65532:65532",
+        core::Artifact {
+          arch: None,
+          from: "target/acme".to_string(),
+          to: Some("/usr/bin/acme".to_string()),
+          mode: Some(0o755),
+          owner: Some(core::Owner {
+            user: core::Id::Numeric(65532),
+            group: Some(core::Id::Numeric(65532)),
+          }),
+          keep_symlinks: false,
+          exclude: vec![],
+          gitignore: false,
+          layer: None,
+        },
+      ),
+      (
+        "static/*.{css,js}:/srv/www/,owner=nginx",
+        core::Artifact {
+          arch: None,
+          from: "static/*.{css,js}".to_string(),
+          to: Some("/srv/www/".to_string()),
+          mode: None,
+          owner: Some(core::Owner {
+            user: core::Id::Name("nginx".to_string()),
+            group: None,
+          }),
+          keep_symlinks: false,
+          exclude: vec![],
+          gitignore: false,
+          layer: None,
+        },
+      ),
+      (
+        "src:/usr/src/acme/,exclude=**/target,exclude=*.bak",
+        core::Artifact {
+          arch: None,
+          from: "src".to_string(),
+          to: Some("/usr/src/acme/".to_string()),
+          mode: None,
+          owner: None,
+          keep_symlinks: false,
+          exclude: vec!["**/target".to_string(), "*.bak".to_string()],
+          gitignore: false,
+          layer: None,
+        },
+      ),
+      (
+        "src:/usr/src/acme/,exclude={target,vendor}/**,mode=0644",
+        core::Artifact {
+          arch: None,
+          from: "src".to_string(),
+          to: Some("/usr/src/acme/".to_string()),
+          mode: Some(0o644),
+          owner: None,
+          keep_symlinks: false,
+          exclude: vec!["{target,vendor}/**".to_string()],
+          gitignore: false,
+          layer: None,
+        },
+      ),
+    ];
+
+    for (repr, want) in cases {
+      let got = cli::artifact_from_string(repr).unwrap();
+      assert_eq!(got, want);
+    }
+
+    for repr in [
+      "app.conf,mode=999",
+      "app.conf,owner=",
+      "app.conf,owner=1:",
+      "app.conf,symlinks=copy",
+      "app.conf,layer=",
+      "app.conf,exclude=",
+    ] {
+      let res = cli::artifact_from_string(repr);
+      assert_eq!(res.unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+  }
+
+  #[test]
+  fn spec_from_string() {
+    let cases = vec![
+      (
+        "/usr/bin/acme -> /opt/acme/bin/acme",
+        core::Symlink {
+          arch: None,
+          path: "/usr/bin/acme".to_string(),
+          target: "/opt/acme/bin/acme".to_string(),
+        },
+      ),
+      (
+        "arm64:/usr/lib/libacme.so->libacme.so.1",
+        core::Symlink {
+          arch: Some(core::Arch::Arm64),
+          path: "/usr/lib/libacme.so".to_string(),
+          target: "libacme.so.1".to_string(),
+        },
+      ),
+    ];
+
+    for (repr, want) in cases {
+      match cli::spec_from_string(repr).unwrap() {
+        cli::Spec::Symlink(got) => assert_eq!(got, want),
+        other => panic!("{:?}", other),
+      }
+    }
+
+    match cli::spec_from_string("lib/:/usr/lib/,symlinks=keep").unwrap() {
+      cli::Spec::Artifact(got) => assert!(got.keep_symlinks),
+      other => panic!("{:?}", other),
+    }
+    assert!(cli::spec_from_string("/usr/bin/acme -> ").is_err());
+  }
+
   #[test]
   fn to_core() {
     let base = "registry.access.redhat.com/ubi8/ubi";
     let image = "quay.io/localhost/example";
     let input = cli::Build {
-      base: base.to_string(),
-      image: image.to_string(),
+      target: None,
+      file: None,
+      base: Some(base.to_string()),
+      image: Some(image.to_string()),
       artifacts: vec![
         "relative/file.txt".to_string(),
         "/absolute/file.txt".to_string(),
@@ -236,8 +898,21 @@ mod tests {
       ],
       arch: vec!["amd64".to_string(), "arm64".to_string()],
       tags: vec!["latest".to_string(), "v1".to_string(), "v1.1".to_string()],
+      timestamp: Some(1650000000),
+      layering: None,
+      gitignore: false,
+      sbom: None,
+      sign: None,
+      base_key: None,
+      verify_reproducible: false,
+      dry_run: false,
+      jobs: None,
+      config: Default::default(),
     };
-    let got = input.to_core();
+    let mut got = input.targets().unwrap();
+    assert_eq!(got.len(), 1);
+    let (name, got) = got.remove(0);
+    assert_eq!(name, image);
     let want = core::build::Build {
       base: base.to_string(),
       image: core::build::ImageName {
@@ -248,40 +923,257 @@ mod tests {
           "v1.1".to_string(),
         ]),
       },
-      artifacts: HashSet::from([
+      artifacts: vec![
         core::Artifact {
           arch: None,
-          from: "/absolute/file.txt".to_string(),
+          from: "relative/file.txt".to_string(),
           to: None,
+          mode: None,
+          owner: None,
+          keep_symlinks: false,
+          exclude: vec![],
+          gitignore: false,
+          layer: None,
         },
         core::Artifact {
           arch: None,
-          from: "relative/file.txt".to_string(),
+          from: "/absolute/file.txt".to_string(),
           to: None,
+          mode: None,
+          owner: None,
+          keep_symlinks: false,
+          exclude: vec![],
+          gitignore: false,
+          layer: None,
         },
         core::Artifact {
           arch: None,
           from: "file.txt".to_string(),
           to: Some("/usr/lib/renamed.txt".to_string()),
+          mode: None,
+          owner: None,
+          keep_symlinks: false,
+          exclude: vec![],
+          gitignore: false,
+          layer: None,
         },
         core::Artifact {
           arch: None,
           from: "target/*.jar".to_string(),
           to: Some("/usr/lib/app".to_string()),
+          mode: None,
+          owner: None,
+          keep_symlinks: false,
+          exclude: vec![],
+          gitignore: false,
+          layer: None,
         },
         core::Artifact {
           arch: Some(core::Arch::Amd64),
           from: "target/acme-linux-amd64".to_string(),
           to: Some("/usr/bin/acme".to_string()),
+          mode: None,
+          owner: None,
+          keep_symlinks: false,
+          exclude: vec![],
+          gitignore: false,
+          layer: None,
         },
         core::Artifact {
           arch: Some(core::Arch::Arm64),
           from: "target/acme-linux-arm64".to_string(),
           to: Some("/usr/bin/acme".to_string()),
+          mode: None,
+          owner: None,
+          keep_symlinks: false,
+          exclude: vec![],
+          gitignore: false,
+          layer: None,
         },
-      ]),
+      ],
       arch: HashSet::from([core::Arch::Amd64, core::Arch::Arm64]),
+      timestamp: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1650000000)),
+      config: Default::default(),
+      symlinks: vec![],
+      layering: core::build::Layering::Single,
+      sbom: None,
     };
     assert_eq!(got, want);
   }
+
+  #[test]
+  fn targets_from_file() {
+    let dir = std::env::temp_dir().join("ocilot-build-targets");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("ocilot.toml");
+    std::fs::write(
+      &path,
+      r#"
+[images.app]
+base = "gcr.io/distroless/static-debian11"
+image = "quay.io/acme/app"
+tags = ["latest"]
+gitignore = true
+sbom = "cyclonedx"
+artifacts = [
+  { from = "target/acme", to = "/usr/bin/acme", mode = "0755" },
+  { from = "etc", to = "/etc/acme/", exclude = ["*.bak"] },
+]
+config = { labels = ["tier=backend"] }
+
+[images.docs]
+base = "nginx"
+image = "quay.io/acme/docs"
+artifacts = ["site/index.html:/usr/share/nginx/html/"]
+
+[images.web]
+base = "target:app"
+image = "quay.io/acme/web"
+artifacts = ["static:/srv/www"]
+"#,
+    )
+    .unwrap();
+    let args = <Args as Parser>::try_parse_from([
+      "app",
+      "app",
+      "--file",
+      path.to_str().unwrap(),
+      "--tag",
+      "v2",
+      "--label",
+      "version=2",
+    ])
+    .unwrap();
+
+    let mut got = args.build.targets().unwrap();
+
+    assert_eq!(got.len(), 1);
+    let (name, got) = got.remove(0);
+    assert_eq!(name, "app");
+    assert_eq!(got.base, "gcr.io/distroless/static-debian11");
+    assert_eq!(got.image.tags, HashSet::from(["v2".to_string()]));
+    assert_eq!(got.sbom, Some(core::build::Sbom::CycloneDx));
+    assert_eq!(
+      got.artifacts,
+      vec![
+        core::Artifact {
+          arch: None,
+          from: "target/acme".to_string(),
+          to: Some("/usr/bin/acme".to_string()),
+          mode: Some(0o755),
+          owner: None,
+          keep_symlinks: false,
+          exclude: vec![],
+          gitignore: true,
+          layer: None,
+        },
+        core::Artifact {
+          arch: None,
+          from: "etc".to_string(),
+          to: Some("/etc/acme/".to_string()),
+          mode: None,
+          owner: None,
+          keep_symlinks: false,
+          exclude: vec!["*.bak".to_string()],
+          gitignore: true,
+          layer: None,
+        },
+      ]
+    );
+    assert_eq!(
+      got.config.labels,
+      Change::Append(BTreeMap::from([
+        ("tier".to_string(), "backend".to_string()),
+        ("version".to_string(), "2".to_string()),
+      ]))
+    );
+
+    let args = <Args as Parser>::try_parse_from([
+      "app",
+      "--file",
+      path.to_str().unwrap(),
+    ])
+    .unwrap();
+    let names: Vec<String> = args
+      .build
+      .targets()
+      .unwrap()
+      .into_iter()
+      .map(|(name, _)| name)
+      .collect();
+    assert_eq!(names, vec!["app", "docs", "web"]);
+
+    let args = <Args as Parser>::try_parse_from([
+      "app",
+      "missing",
+      "--file",
+      path.to_str().unwrap(),
+    ])
+    .unwrap();
+    assert!(args.build.targets().is_err());
+
+    let args = <Args as Parser>::try_parse_from([
+      "app",
+      "--file",
+      path.to_str().unwrap(),
+      "--image",
+      "quay.io/acme/other",
+    ])
+    .unwrap();
+    assert!(args.build.targets().is_err());
+
+    let args = <Args as Parser>::try_parse_from([
+      "app",
+      "docs",
+      "--file",
+      path.to_str().unwrap(),
+      "--base",
+      "httpd",
+    ])
+    .unwrap();
+    let got = args.build.targets().unwrap();
+    assert_eq!(got.len(), 1);
+    assert_eq!(got[0].1.base, "httpd");
+
+    let args = <Args as Parser>::try_parse_from([
+      "app",
+      "web",
+      "--file",
+      path.to_str().unwrap(),
+      "--tag",
+      "v2",
+      "--artifact",
+      "x:/tmp/x",
+      "--label",
+      "version=2",
+      "--timestamp",
+      "1",
+    ])
+    .unwrap();
+    let got = args.build.targets().unwrap();
+    let names: Vec<&str> = got.iter().map(|(n, _)| n.as_str()).collect();
+    assert_eq!(names, vec!["web", "app"]);
+    let (web, app) = (&got[0].1, &got[1].1);
+    assert_eq!(web.base, "local:quay.io/acme/app");
+    assert_eq!(web.image.tags, HashSet::from(["v2".to_string()]));
+    assert_eq!(web.artifacts[0].from, "x");
+    assert!(web.timestamp.is_some());
+    assert_eq!(app.image.tags, HashSet::from(["latest".to_string()]));
+    assert_eq!(app.artifacts.len(), 2);
+    assert_eq!(app.artifacts[0].from, "target/acme");
+    assert_eq!(app.timestamp, None);
+    assert_eq!(
+      app.config.labels,
+      Change::Append(BTreeMap::from([(
+        "tier".to_string(),
+        "backend".to_string()
+      )]))
+    );
+  }
+
+  #[derive(Debug, Parser)]
+  struct Args {
+    #[clap(flatten)]
+    build: cli::Build,
+  }
 }
@@ -0,0 +1,27 @@
+use clap::Args;
+use tracing::debug;
+
+use crate::cli::{args, build, error};
+
+#[derive(Debug, Args)]
+pub struct Fingerprint {
+  #[clap(flatten)]
+  build: build::Build,
+}
+
+impl args::Executable for Fingerprint {
+  fn execute(&self, args: &args::Args) -> error::Result<()> {
+    let cmd = build::new_command(args)?;
+    debug!("Computing fingerprint...");
+    let targets = self.build.targets()?;
+    for (name, build) in &targets {
+      let fingerprint = cmd.fingerprint(build)?;
+      if targets.len() == 1 {
+        println!("{}", fingerprint);
+      } else {
+        println!("{}  {}", fingerprint, name);
+      }
+    }
+    Ok(())
+  }
+}
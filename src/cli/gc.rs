@@ -0,0 +1,26 @@
+use clap::Args;
+use ocilot_core as core;
+use ocilot_oci::{cache, config};
+use tracing::info;
+
+use crate::cli::{args, error};
+
+#[derive(Debug, Args)]
+pub struct Gc {}
+
+impl args::Executable for Gc {
+  fn execute(&self, args: &args::Args) -> error::Result<()> {
+    let cache = cache::HomeBased {
+      config: Box::new(config::Config {
+        workdir: args.ocilot_dir()?,
+      }) as Box<dyn core::oci::Config>,
+    };
+    let collected = cache.collect_garbage()?;
+    info!(
+      blobs = collected.blobs,
+      bytes = collected.bytes,
+      "Unreferenced blobs removed from the cache"
+    );
+    Ok(())
+  }
+}
@@ -0,0 +1,269 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{Error, ErrorKind};
+
+use ocilot_core::oci::{Change, ConfigChanges};
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+/// Changes to the runtime config of the image. The values are inherited from
+/// the base image, unless given or reset.
+#[derive(clap::Args, Debug, Default, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ImageConfig {
+  /// A command to run when the container starts. Repeat the option for each
+  /// argument. Replaces the base image entrypoint, and drops its command.
+  #[clap(long, multiple_occurrences = true)]
+  entrypoint: Vec<String>,
+  /// Default arguments for the entrypoint. Repeat the option for each
+  /// argument. Replaces the base image command.
+  #[clap(long, multiple_occurrences = true)]
+  cmd: Vec<String>,
+  /// Environment variables, in form "KEY=VALUE". Repeat the option to add
+  /// multiple values. Merged into the base image ones.
+  #[clap(short = 'e', long, multiple_occurrences = true)]
+  env: Vec<String>,
+  /// A working directory of the entrypoint.
+  #[clap(long)]
+  workdir: Option<String>,
+  /// A user, and optionally a group, to run the entrypoint as.
+  #[clap(long)]
+  user: Option<String>,
+  /// Labels, in form "KEY=VALUE". Repeat the option to add multiple values.
+  /// Merged into the base image ones.
+  #[clap(long = "label", multiple_occurrences = true)]
+  labels: Vec<String>,
+  /// Ports to expose, in form "port[/protocol]", like "8080/tcp". Repeat the
+  /// option to add multiple values. Merged into the base image ones.
+  #[clap(long, multiple_occurrences = true)]
+  expose: Vec<String>,
+  /// Paths to mark as volumes. Repeat the option to add multiple values.
+  /// Merged into the base image ones.
+  #[clap(long = "volume", multiple_occurrences = true)]
+  volumes: Vec<String>,
+  /// A signal to stop the container with, like "SIGTERM".
+  #[clap(long)]
+  stop_signal: Option<String>,
+  /// Config values to drop from the base image, before applying the given
+  /// ones. Repeat the option to add multiple values.
+  #[clap(arg_enum, long, multiple_occurrences = true)]
+  reset: Vec<ConfigKey>,
+}
+
+#[derive(
+  Copy, Clone, PartialEq, Eq, Debug, clap::ArgEnum, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConfigKey {
+  Entrypoint,
+  Cmd,
+  Env,
+  Workdir,
+  User,
+  Labels,
+  Ports,
+  Volumes,
+  StopSignal,
+}
+
+impl ImageConfig {
+  pub fn to_core(&self) -> Result<ConfigChanges, Error> {
+    let non_empty = |v: &Vec<String>| Some(v.clone()).filter(|v| !v.is_empty());
+    let env = self
+      .env
+      .iter()
+      .map(key_value_from_string)
+      .collect::<Result<Vec<_>, Error>>()?;
+    let labels = self
+      .labels
+      .iter()
+      .map(key_value_from_string)
+      .collect::<Result<BTreeMap<_, _>, Error>>()?;
+    let ports = self
+      .expose
+      .iter()
+      .map(port_from_string)
+      .collect::<Result<BTreeSet<_>, Error>>()?;
+    let volumes: BTreeSet<_> = self.volumes.iter().cloned().collect();
+    Ok(ConfigChanges {
+      entrypoint: self
+        .replace(ConfigKey::Entrypoint, non_empty(&self.entrypoint)),
+      cmd: self.replace(ConfigKey::Cmd, non_empty(&self.cmd)),
+      env: self.merge(ConfigKey::Env, Some(env).filter(|v| !v.is_empty())),
+      workdir: self.replace(ConfigKey::Workdir, self.workdir.clone()),
+      user: self.replace(ConfigKey::User, self.user.clone()),
+      labels: self
+        .merge(ConfigKey::Labels, Some(labels).filter(|v| !v.is_empty())),
+      exposed_ports: self
+        .merge(ConfigKey::Ports, Some(ports).filter(|v| !v.is_empty())),
+      volumes: self
+        .merge(ConfigKey::Volumes, Some(volumes).filter(|v| !v.is_empty())),
+      stop_signal: self
+        .replace(ConfigKey::StopSignal, self.stop_signal.clone()),
+    })
+  }
+
+  /// A change of a value, that replaces the base image one.
+  fn replace<T>(&self, key: ConfigKey, value: Option<T>) -> Change<T> {
+    match value {
+      Some(value) => Change::Set(value),
+      None if self.reset.contains(&key) => Change::Reset,
+      None => Change::Inherit,
+    }
+  }
+
+  /// A change of a value, that merges into the base image one, unless reset.
+  fn merge<T>(&self, key: ConfigKey, value: Option<T>) -> Change<T> {
+    let reset = self.reset.contains(&key);
+    match value {
+      Some(value) if reset => Change::Set(value),
+      Some(value) => Change::Append(value),
+      None if reset => Change::Reset,
+      None => Change::Inherit,
+    }
+  }
+}
+
+/// Lays the changes given by `over` on top of the `under` ones. Values set
+/// by `over` win, and values it appends are added to the `under` ones.
+pub fn layered(under: ConfigChanges, over: ConfigChanges) -> ConfigChanges {
+  ConfigChanges {
+    entrypoint: replaced(under.entrypoint, over.entrypoint),
+    cmd: replaced(under.cmd, over.cmd),
+    env: merged(under.env, over.env),
+    workdir: replaced(under.workdir, over.workdir),
+    user: replaced(under.user, over.user),
+    labels: merged(under.labels, over.labels),
+    exposed_ports: merged(under.exposed_ports, over.exposed_ports),
+    volumes: merged(under.volumes, over.volumes),
+    stop_signal: replaced(under.stop_signal, over.stop_signal),
+  }
+}
+
+fn replaced<T>(under: Change<T>, over: Change<T>) -> Change<T> {
+  match over {
+    Change::Inherit => under,
+    over => over,
+  }
+}
+
+fn merged<T>(under: Change<T>, over: Change<T>) -> Change<T>
+where
+  T: IntoIterator + Extend<T::Item>,
+{
+  match (under, over) {
+    (Change::Set(mut values), Change::Append(more)) => {
+      values.extend(more);
+      Change::Set(values)
+    }
+    (Change::Append(mut values), Change::Append(more)) => {
+      values.extend(more);
+      Change::Append(values)
+    }
+    (Change::Reset, Change::Append(more)) => Change::Set(more),
+    (under, over) => replaced(under, over),
+  }
+}
+
+fn key_value_from_string(repr: &String) -> Result<(String, String), Error> {
+  match repr.split_once('=') {
+    Some((key, value)) if !key.is_empty() => {
+      Ok((key.to_string(), value.to_string()))
+    }
+    _ => Err(Error::new(
+      ErrorKind::InvalidInput,
+      format!("not a KEY=VALUE: {:?}", repr),
+    )),
+  }
+}
+
+fn port_from_string(repr: &String) -> Result<String, Error> {
+  let (port, proto) = repr.split_once('/').unwrap_or((repr, "tcp"));
+  let valid_proto = matches!(proto, "tcp" | "udp" | "sctp");
+  match port.parse::<u16>() {
+    Ok(port) if valid_proto => Ok(format!("{}/{}", port, proto)),
+    _ => Err(Error::new(
+      ErrorKind::InvalidInput,
+      format!("bad port: {:?}", repr),
+    )),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::{BTreeMap, BTreeSet};
+
+  use clap::Parser;
+  use ocilot_core::oci::{Change, ConfigChanges};
+
+  use crate::cli::image_config::ImageConfig;
+
+  #[derive(Debug, Parser)]
+  struct Args {
+    #[clap(flatten)]
+    config: ImageConfig,
+  }
+
+  #[test]
+  fn to_core() {
+    let args = <Args as Parser>::try_parse_from([
+      "app",
+      "--entrypoint",
+      "/usr/bin/acme",
+      "--entrypoint",
+      "serve",
+      "-e",
+      "ACME_HOME=/opt/acme",
+      "--label",
+      "org.opencontainers.image.title=acme",
+      "--expose",
+      "8080",
+      "--expose",
+      "9090/udp",
+      "--user",
+      "65532:65532",
+      "--reset",
+      "labels",
+      "--reset",
+      "volumes",
+    ])
+    .unwrap();
+
+    let got = args.config.to_core().unwrap();
+
+    assert_eq!(
+      got,
+      ConfigChanges {
+        entrypoint: Change::Set(vec![
+          "/usr/bin/acme".to_string(),
+          "serve".to_string()
+        ]),
+        cmd: Change::Inherit,
+        env: Change::Append(vec![(
+          "ACME_HOME".to_string(),
+          "/opt/acme".to_string()
+        )]),
+        workdir: Change::Inherit,
+        user: Change::Set("65532:65532".to_string()),
+        labels: Change::Set(BTreeMap::from([(
+          "org.opencontainers.image.title".to_string(),
+          "acme".to_string()
+        )])),
+        exposed_ports: Change::Append(BTreeSet::from([
+          "8080/tcp".to_string(),
+          "9090/udp".to_string()
+        ])),
+        volumes: Change::Reset,
+        stop_signal: Change::Inherit,
+      }
+    );
+  }
+
+  #[test]
+  fn invalid() {
+    for flags in [["--env", "NOVALUE"], ["--expose", "http"]] {
+      let args =
+        <Args as Parser>::try_parse_from(["app", flags[0], flags[1]]).unwrap();
+      assert!(args.config.to_core().is_err());
+    }
+  }
+}
@@ -1,5 +1,9 @@
+use std::time::SystemTime;
+
 use clap::Args;
-use tracing::{info, trace};
+use ocilot_core as core;
+use ocilot_oci::{cache, config};
+use tracing::trace;
 
 use crate::cli;
 
@@ -9,7 +13,36 @@ pub struct List {}
 impl cli::args::Executable for List {
   fn execute(&self, args: &cli::args::Args) -> cli::error::Result<()> {
     trace!(args = ?args);
-    info!("Listing: {:?}", self);
+    let cache = cache::HomeBased {
+      config: Box::new(config::Config {
+        workdir: args.ocilot_dir()?,
+      }) as Box<dyn core::oci::Config>,
+    };
+    let mut images = cache.images()?;
+    images.sort_by(|a, b| (&a.image, b.created).cmp(&(&b.image, a.created)));
+    for image in images {
+      let name = match image.tags.as_slice() {
+        [] => image.image.clone(),
+        tags => format!("{}:{}", image.image, tags.join(",")),
+      };
+      println!(
+        "{}  {:<6}  {:<20}  {:>10}  {}",
+        image.digest,
+        image.origin,
+        created(image.created),
+        image.size,
+        if name.is_empty() { "<unnamed>" } else { &name },
+      );
+    }
     Ok(())
   }
 }
+
+/// The creation time, like "2022-02-22T10:00:00Z", or a dash, when unknown.
+fn created(time: Option<SystemTime>) -> String {
+  match time {
+    Some(time) => chrono::DateTime::<chrono::Utc>::from(time)
+      .to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+    None => "-".to_string(),
+  }
+}
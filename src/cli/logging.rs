@@ -102,6 +102,26 @@ pub fn configured(cfg: Config, f: impl FnOnce() -> Result<()>) -> Result<()> {
             logfile = ?logfile_path,
             "Bug found: {:?}", bug
           ),
+          core::error::Error::NotReproducible { first, second } => error!(
+            first = ?first,
+            second = ?second,
+            "Build isn't reproducible"
+          ),
+          core::error::Error::BatchFailed { failed, total } => error!(
+            failed = ?failed,
+            total = ?total,
+            "Some builds failed"
+          ),
+          core::error::Error::UnverifiedBase { image, digest } => error!(
+            image = ?image,
+            digest = ?digest,
+            "Base image signature isn't verified"
+          ),
+          core::error::Error::PolicyViolation { image, rule } => error!(
+            image = ?image,
+            rule = %rule,
+            "Trust policy violated"
+          ),
         },
       },
     }
@@ -1,7 +1,16 @@
 pub mod args;
 pub mod build;
 pub mod error;
+pub mod fingerprint;
+pub mod gc;
+mod image_config;
 pub mod list;
 mod logging;
+mod plan;
+mod policy;
+mod progress;
+mod project;
 pub mod publish;
+pub mod schema;
+pub mod sign;
 mod verbosity;
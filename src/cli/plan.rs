@@ -0,0 +1,153 @@
+use std::path::Path;
+
+use ocilot_core::build::Plan;
+
+/// Renders the plan of a build target as a human readable listing.
+pub fn render(name: &str, plan: &Plan, cwd: &Path) -> String {
+  let mut tags: Vec<&str> =
+    plan.image.tags.iter().map(String::as_str).collect();
+  tags.sort_unstable();
+  let archs = if plan.archs.is_empty() {
+    "(from base image)".to_string()
+  } else {
+    let archs: Vec<String> = plan.archs.iter().map(|a| a.to_string()).collect();
+    archs.join(", ")
+  };
+  let mut out = format!("Target: {}\n", name);
+  out.push_str(&format!("Image:  {}\n", plan.image.image));
+  if !tags.is_empty() {
+    out.push_str(&format!("Tags:   {}\n", tags.join(", ")));
+  }
+  out.push_str(&format!("Base:   {}\n", plan.base));
+  out.push_str(&format!("Arch:   {}\n", archs));
+  let mut rows = vec![[
+    "LAYER".to_string(),
+    "ARCH".to_string(),
+    "MODE".to_string(),
+    "OWNER".to_string(),
+    "SIZE".to_string(),
+    "DESTINATION".to_string(),
+    "SOURCE".to_string(),
+  ]];
+  for file in &plan.files {
+    rows.push([
+      file.layer.to_string(),
+      file
+        .arch
+        .as_ref()
+        .map(|a| a.to_string())
+        .unwrap_or_else(|| "*".to_string()),
+      format!("{:04o}", file.mode),
+      file
+        .owner
+        .as_ref()
+        .map(|o| o.to_string())
+        .unwrap_or_else(|| "0:0".to_string()),
+      file.size.to_string(),
+      file.to.clone(),
+      match &file.symlink {
+        Some(target) => format!("-> {}", target),
+        None => file
+          .from
+          .strip_prefix(cwd)
+          .unwrap_or(&file.from)
+          .display()
+          .to_string(),
+      },
+    ]);
+  }
+  let mut widths = [0; 7];
+  for row in &rows {
+    for (width, cell) in widths.iter_mut().zip(row) {
+      *width = (*width).max(cell.len());
+    }
+  }
+  for row in rows {
+    let cells: Vec<String> = row
+      .iter()
+      .zip(widths)
+      .map(|(cell, width)| format!("{:width$}", cell, width = width))
+      .collect();
+    out.push_str(&format!("  {}\n", cells.join("  ").trim_end()));
+  }
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::HashSet;
+  use std::path::{Path, PathBuf};
+
+  use ocilot_core as core;
+  use ocilot_core::build::{ImageName, Plan, PlannedFile};
+
+  use crate::cli::plan;
+
+  #[test]
+  fn render() {
+    let plan = Plan {
+      base: "gcr.io/distroless/static-debian11".to_string(),
+      image: ImageName {
+        image: "quay.io/acme/app".to_string(),
+        tags: HashSet::from(["v1".to_string(), "latest".to_string()]),
+      },
+      archs: vec![core::Arch::Amd64],
+      files: vec![
+        PlannedFile {
+          layer: 0,
+          arch: Some(core::Arch::Amd64),
+          from: PathBuf::from("/src/acme/acme.toml"),
+          to: "/etc/acme.toml".to_string(),
+          mode: 0o644,
+          owner: None,
+          size: 120,
+          symlink: None,
+        },
+        PlannedFile {
+          layer: 0,
+          arch: Some(core::Arch::Amd64),
+          from: PathBuf::new(),
+          to: "/usr/bin/acme".to_string(),
+          mode: 0o777,
+          owner: None,
+          size: 0,
+          symlink: Some("/opt/acme/bin/acme".to_string()),
+        },
+        PlannedFile {
+          layer: 1,
+          arch: Some(core::Arch::Amd64),
+          from: PathBuf::from("/src/acme/target/acme"),
+          to: "/usr/local/bin/acme".to_string(),
+          mode: 0o755,
+          owner: Some(core::Owner {
+            user: core::Id::Numeric(65532),
+            group: Some(core::Id::Name("acme".to_string())),
+          }),
+          size: 5242880,
+          symlink: None,
+        },
+      ],
+    };
+
+    let got = plan::render("app", &plan, Path::new("/src/acme"));
+
+    assert_eq!(
+      got,
+      concat!(
+        "Target: app\n",
+        "Image:  quay.io/acme/app\n",
+        "Tags:   latest, v1\n",
+        "Base:   gcr.io/distroless/static-debian11\n",
+        "Arch:   amd64\n",
+        "  LAYER  ARCH   MODE  OWNER       SIZE     DESTINATION          ",
+        "SOURCE\n",
+        "  0      amd64  0644  0:0         120      /etc/acme.toml       ",
+        "acme.toml\n",
+        "  0      amd64  0777  0:0         0        /usr/bin/acme        ",
+        "-> /opt/acme/bin/acme\n",
+        "  1      amd64  0755  65532:acme  5242880  /usr/local/bin/acme  ",
+        "target/acme\n",
+      )
+    );
+  }
+}
@@ -0,0 +1,127 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ocilot_core as core;
+use ocilot_oci::signing;
+use serde::Deserialize;
+
+use crate::cli::error;
+
+/// A trust policy file, enforced on every base image pulled, and every image
+/// signed, like:
+///
+/// ```toml
+/// allowed = ["gcr.io/distroless", "quay.io/acme"]
+/// require-digest = true
+/// forbid-latest = true
+///
+/// [[signatures]]
+/// scope = "quay.io/acme"
+/// keys = ["keys/acme.pub"]
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+struct PolicyFile {
+  /// Registries or repositories the images may come from, or be signed
+  /// within. Any, when empty.
+  allowed: Vec<String>,
+  /// Whether the base images need to be pinned by digest.
+  require_digest: bool,
+  /// Whether the "latest" tag is refused, even when implied.
+  forbid_latest: bool,
+  /// Scopes whose images need a valid signature.
+  signatures: Vec<Signatures>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Signatures {
+  /// A registry or repository. The most specific scope of an image applies.
+  scope: String,
+  /// Public key files, relative to the policy file. A signature verifying
+  /// against any of them is enough.
+  keys: Vec<PathBuf>,
+}
+
+/// Reads the policy file, loading the keys it refers to.
+pub(crate) fn load(path: &Path) -> error::Result<core::policy::Policy> {
+  let content = fs::read_to_string(path).map_err(core::error::Error::from)?;
+  let file: PolicyFile = toml::from_str(&content).map_err(|err| {
+    core::error::Error::InvalidInput {
+      message: format!("{}: {}", path.display(), err),
+      cause: Some(Box::new(err)),
+    }
+  })?;
+  let dir = path.parent().unwrap_or_else(|| Path::new(""));
+  let mut signed = Vec::new();
+  for rule in file.signatures {
+    if rule.keys.is_empty() {
+      return Err(
+        core::error::Error::invalid_input(&format!(
+          "{}: no keys for the signatures of {}",
+          path.display(),
+          rule.scope
+        ))
+        .into(),
+      );
+    }
+    let mut verifiers: Vec<Box<dyn core::oci::Verifier>> = Vec::new();
+    for key in rule.keys {
+      verifiers
+        .push(Box::new(signing::KeyVerifier::from_file(&dir.join(key))?));
+    }
+    signed.push(core::policy::SignedScope {
+      scope: rule.scope,
+      verifiers,
+    });
+  }
+  Ok(core::policy::Policy {
+    allowed: file.allowed,
+    require_digest: file.require_digest,
+    forbid_latest: file.forbid_latest,
+    signed,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::cli::error::Cause;
+  use crate::cli::policy;
+
+  #[test]
+  fn load() {
+    let dir = std::env::temp_dir().join("ocilot-policy-load");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("policy.toml");
+    std::fs::write(
+      &path,
+      r#"
+allowed = ["gcr.io/distroless", "quay.io/acme"]
+require-digest = true
+forbid-latest = true
+"#,
+    )
+    .unwrap();
+
+    let got = policy::load(&path).unwrap();
+
+    assert_eq!(got.allowed, vec!["gcr.io/distroless", "quay.io/acme"]);
+    assert!(got.require_digest);
+    assert!(got.forbid_latest);
+    assert!(got.signed.is_empty());
+
+    std::fs::write(
+      &path,
+      r#"
+[[signatures]]
+scope = "quay.io/acme"
+keys = ["missing.pub"]
+"#,
+    )
+    .unwrap();
+
+    let err = policy::load(&path).unwrap_err();
+
+    assert!(matches!(err.cause, Cause::Core(_)), "{:?}", err);
+  }
+}
@@ -0,0 +1,71 @@
+use ocilot_core::build::ImageName;
+use ocilot_core::observe::{BuildObserver, Event};
+use tracing::{debug, info};
+
+/// Renders the progress of the builds as log events, with the event details
+/// as fields, so the JSON output carries them as is.
+#[derive(Debug)]
+pub(crate) struct Progress {}
+
+impl BuildObserver for Progress {
+  fn observe(&self, im: &ImageName, event: &Event) {
+    let image = &im.image;
+    match event {
+      Event::ArtifactResolved { from, files } => {
+        debug!(image = ?image, from = ?from, files, "Artifact resolved");
+      }
+      Event::BaseFetchStarted { image: base } => {
+        info!(image = ?image, base = ?base, "Fetching base image...");
+      }
+      Event::BaseFetchProgress {
+        image: base,
+        pulled,
+        total,
+      } => {
+        debug!(
+          image = ?image,
+          base = ?base,
+          pulled,
+          total,
+          "Base image layers pulled"
+        );
+      }
+      Event::BaseFetchFinished {
+        image: base,
+        digest,
+      } => {
+        info!(
+          image = ?image,
+          base = ?base,
+          digest = ?digest,
+          "Base image fetched"
+        );
+      }
+      Event::LayerWritten { arch, digest, size } => {
+        let arch = arch.as_ref().map(ToString::to_string);
+        info!(
+          image = ?image,
+          arch = ?arch,
+          digest = ?digest,
+          size,
+          "Layer written"
+        );
+      }
+      Event::CacheHit {
+        digest,
+        fingerprint,
+      } => {
+        info!(
+          image = ?image,
+          digest = ?digest,
+          fingerprint = ?fingerprint,
+          "Image found in cache"
+        );
+      }
+      Event::ManifestBuilt { digest } => {
+        info!(image = ?image, digest = ?digest, "Image manifest built");
+      }
+      _ => debug!(image = ?image, event = ?event, "Build event"),
+    }
+  }
+}
@@ -0,0 +1,321 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use ocilot_core as core;
+use schemars::JsonSchema;
+use serde::Deserialize;
+
+use crate::cli::build::{Layering, Sbom};
+use crate::cli::error;
+use crate::cli::image_config::ImageConfig;
+
+/// The name of the build file, looked up in the current directory.
+pub const FILENAME: &str = "ocilot.toml";
+
+/// Prefixes a base image referring to another target of the build file, like
+/// "target:runtime". The target is built first, and its image is used as is,
+/// without pushing it.
+pub const TARGET_BASE: &str = "target:";
+
+/// A build file, describing the images of a project.
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Project {
+  /// Images to build, by their target name.
+  #[serde(default)]
+  pub images: BTreeMap<String, Target>,
+}
+
+/// An image to build. The values can be overridden by command line options.
+#[derive(Debug, Default, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct Target {
+  /// A base image to build upon. Short image name will resolve to docker.io
+  /// Another target is referred to as "target:<name>", and an image built
+  /// before, found in the local cache, as "local:<image>[:tag]", or
+  /// "local:<image>@<digest>".
+  pub base: Option<String>,
+  /// Image name to build, without tags. Short image name will resolve to
+  /// docker.io
+  pub image: Option<String>,
+  /// Tags to assign to the built image.
+  pub tags: Vec<String>,
+  /// Architectures to build the image for. If not given the architectures
+  /// from base image will be used.
+  pub arch: Vec<String>,
+  /// Artifacts to add on top of base image.
+  pub artifacts: Vec<Artifact>,
+  /// A fixed creation time of the image and its files, as Unix timestamp.
+  pub timestamp: Option<u64>,
+  /// Changes to the runtime config of the image.
+  pub config: ImageConfig,
+  /// How the artifacts are split into layers.
+  pub layering: Option<Layering>,
+  /// Whether the paths listed in .gitignore files are left out of the
+  /// artifacts too, like the ones of .ocilotignore files.
+  pub gitignore: Option<bool>,
+  /// The format of a bill of materials to record along the image.
+  pub sbom: Option<Sbom>,
+}
+
+/// An artifact to add on top of base image.
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum Artifact {
+  /// An artifact spec, in the same form as the `--artifact` option takes.
+  Spec(String),
+  /// An artifact, given by its parts.
+  Parts(Parts),
+  /// A symlink to create within the image.
+  Symlink(Symlink),
+}
+
+/// An artifact, given by its parts.
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Parts {
+  /// A file, a directory, or a glob, on the host.
+  pub from: String,
+  /// A file, or a directory (with trailing slash), on the image.
+  pub to: Option<String>,
+  /// The only architecture to add the artifact to.
+  pub arch: Option<String>,
+  /// Octal permission bits, like "0755". Host ones are kept by default.
+  pub mode: Option<String>,
+  /// A user, and optionally a group, like "65532:65532". Names from the
+  /// base image can be used. Root by default.
+  pub owner: Option<String>,
+  /// Whether host symlinks are added as such, instead of being followed.
+  pub keep_symlinks: Option<bool>,
+  /// Glob patterns of paths to leave out, relative to the walked directory.
+  #[serde(default)]
+  pub exclude: Vec<String>,
+  /// A name of the layer to put the files into, shared with other artifacts
+  /// naming it.
+  pub layer: Option<String>,
+}
+
+/// A symlink to create within the image.
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Symlink {
+  /// A path of the link within the image.
+  pub link: String,
+  /// A path the link points to.
+  pub target: String,
+  /// The only architecture to add the link to.
+  pub arch: Option<String>,
+}
+
+impl Project {
+  /// Reads the build file at given path. Returns `None` if it doesn't exist.
+  pub fn read(path: &Path) -> error::Result<Option<Project>> {
+    let content = match fs::read_to_string(path) {
+      Ok(content) => content,
+      Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+      Err(err) => return Err(core::error::Error::from(err).into()),
+    };
+    toml::from_str(&content).map(Some).map_err(|err| {
+      core::error::Error::InvalidInput {
+        message: format!("{}: {}", path.display(), err),
+        cause: Some(Box::new(err)),
+      }
+      .into()
+    })
+  }
+
+  /// Selects the targets to build: the named one, with the targets it's
+  /// built upon, or all of them.
+  pub fn select(
+    &self,
+    name: Option<&str>,
+  ) -> error::Result<Vec<(&String, &Target)>> {
+    let name = match name {
+      None if self.images.is_empty() => {
+        return Err(
+          core::error::Error::invalid_input("no images in build file").into(),
+        )
+      }
+      None => return Ok(self.images.iter().collect()),
+      Some(name) => name,
+    };
+    let mut selected = vec![self.target(name)?];
+    let mut i = 0;
+    while i < selected.len() {
+      if let Some(parent) = selected[i].1.base_target() {
+        if !selected.iter().any(|(name, _)| *name == parent) {
+          selected.push(self.target(parent)?);
+        }
+      }
+      i += 1;
+    }
+    Ok(selected)
+  }
+
+  /// Resolves a base image referring to another target into the local image
+  /// that target builds. Other base images are kept as they are.
+  pub fn resolve_base(&self, base: &str) -> error::Result<String> {
+    let name = match base.strip_prefix(TARGET_BASE) {
+      Some(name) => name,
+      None => return Ok(base.to_string()),
+    };
+    let (_, target) = self.target(name)?;
+    let image = target.image.as_ref().ok_or_else(|| {
+      core::error::Error::invalid_input(&format!(
+        "base target {:?} has no image name",
+        name
+      ))
+    })?;
+    Ok(format!("{}{}", core::build::LOCAL_BASE, image))
+  }
+
+  fn target(&self, name: &str) -> error::Result<(&String, &Target)> {
+    self.images.get_key_value(name).ok_or_else(|| {
+      let known: Vec<&str> = self.images.keys().map(String::as_str).collect();
+      core::error::Error::invalid_input(&format!(
+        "no target {:?} in build file, known are: {}",
+        name,
+        known.join(", ")
+      ))
+      .into()
+    })
+  }
+}
+
+impl Target {
+  /// The name of another target the image is built upon, if any.
+  pub fn base_target(&self) -> Option<&str> {
+    self.base.as_deref()?.strip_prefix(TARGET_BASE)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::Write;
+
+  use crate::cli::project::{Artifact, Parts, Project};
+
+  #[test]
+  fn read() {
+    let dir = std::env::temp_dir().join("ocilot-project-read");
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("ocilot.toml");
+    let mut file = std::fs::File::create(&path).unwrap();
+    file
+      .write_all(
+        br#"
+[images.app]
+base = "gcr.io/distroless/static-debian11"
+image = "quay.io/acme/app"
+tags = ["latest", "v1"]
+arch = ["amd64", "arm64"]
+artifacts = [
+  "target/acme:/usr/bin/acme,mode=0755",
+  { from = "acme.toml", to = "/etc/acme.toml", owner = "65532" },
+]
+
+[images.app.config]
+entrypoint = ["/usr/bin/acme"]
+env = ["ACME_HOME=/opt/acme"]
+reset = ["stop-signal"]
+
+[images.docs]
+base = "nginx"
+image = "quay.io/acme/docs"
+artifacts = ["site/**/*.html:/usr/share/nginx/html/"]
+"#,
+      )
+      .unwrap();
+
+    let project = Project::read(&path).unwrap().unwrap();
+
+    let names: Vec<&String> = project.images.keys().collect();
+    assert_eq!(names, vec!["app", "docs"]);
+    let app = &project.images["app"];
+    assert_eq!(app.tags, vec!["latest", "v1"]);
+    assert_eq!(app.artifacts.len(), 2);
+    match &app.artifacts[1] {
+      Artifact::Parts(Parts { from, owner, .. }) => {
+        assert_eq!(from, "acme.toml");
+        assert_eq!(owner.as_deref(), Some("65532"));
+      }
+      other => panic!("{:?}", other),
+    }
+    let selected = project.select(Some("docs")).unwrap();
+    assert_eq!(selected.len(), 1);
+    assert_eq!(selected[0].0, "docs");
+    assert!(project.select(Some("missing")).is_err());
+    assert_eq!(project.select(None).unwrap().len(), 2);
+
+    assert!(Project::read(&dir.join("missing.toml")).unwrap().is_none());
+    std::fs::write(&path, "[images.app]\nbsae = \"nginx\"\n").unwrap();
+    assert!(Project::read(&path).is_err());
+    std::fs::write(
+      &path,
+      r#"
+[images.app]
+artifacts = [{ from = "etc", excldue = ["*.bak"] }]
+"#,
+    )
+    .unwrap();
+    assert!(Project::read(&path).is_err());
+    std::fs::write(
+      &path,
+      r#"
+[images.app]
+artifacts = [{ link = "/a", target = "/b", mode = "0755" }]
+"#,
+    )
+    .unwrap();
+    assert!(Project::read(&path).is_err());
+  }
+
+  #[test]
+  fn base_targets() {
+    let project: Project = toml::from_str(
+      r#"
+[images.runtime]
+base = "gcr.io/distroless/java17-debian11"
+image = "quay.io/acme/runtime"
+
+[images.app]
+base = "target:runtime"
+image = "quay.io/acme/app"
+
+[images.docs]
+base = "nginx"
+image = "quay.io/acme/docs"
+"#,
+    )
+    .unwrap();
+
+    let selected: Vec<&String> = project
+      .select(Some("app"))
+      .unwrap()
+      .into_iter()
+      .map(|(name, _)| name)
+      .collect();
+
+    assert_eq!(selected, vec!["app", "runtime"]);
+    assert_eq!(
+      project.resolve_base("target:runtime").unwrap(),
+      "local:quay.io/acme/runtime"
+    );
+    assert_eq!(project.resolve_base("nginx").unwrap(), "nginx");
+    assert!(project.resolve_base("target:missing").is_err());
+  }
+
+  #[test]
+  fn schema_is_published() {
+    let schema = schemars::schema_for!(Project);
+    let want = serde_json::to_string_pretty(&schema).unwrap();
+    let published = include_str!("../../ocilot.schema.json");
+    assert_eq!(
+      published.trim_end(),
+      want,
+      "regenerate with: ocilot schema > ocilot.schema.json"
+    );
+  }
+}
@@ -0,0 +1,18 @@
+use clap::Args;
+use ocilot_core as core;
+
+use crate::cli::{args, error, project};
+
+#[derive(Debug, Args)]
+pub struct Schema {}
+
+impl args::Executable for Schema {
+  fn execute(&self, _: &args::Args) -> error::Result<()> {
+    let schema = schemars::schema_for!(project::Project);
+    let json = serde_json::to_string_pretty(&schema).map_err(|err| {
+      core::error::Error::Bug(format!("can't serialize schema: {}", err))
+    })?;
+    println!("{}", json);
+    Ok(())
+  }
+}
@@ -0,0 +1,44 @@
+use std::path::PathBuf;
+
+use clap::Args;
+use ocilot_core as core;
+use ocilot_core::oci::SignatureScheme;
+use ocilot_oci::signing;
+use tracing::info;
+
+use crate::cli::{args, error};
+
+#[derive(Debug, Args)]
+pub struct Sign {
+  /// The image to sign, as pushed to a registry. A tag is resolved to the
+  /// manifest digest it points to.
+  image: String,
+  /// A private key file to sign with. A PEM encoded PKCS#8 key, either ECDSA
+  /// P-256 or ed25519, is expected.
+  #[clap(short = 'k', long)]
+  key: PathBuf,
+  /// Pushes the signature as an OCI 1.1 referrer of the image, instead of
+  /// the "sha256-<digest>.sig" tag.
+  #[clap(long)]
+  referrer: bool,
+}
+
+impl args::Executable for Sign {
+  fn execute(&self, args: &args::Args) -> error::Result<()> {
+    let signer = Box::new(signing::KeySigner::from_file(&self.key)?);
+    let registry = Box::new(args.registry()?);
+    let cmd = core::sign::Command {
+      registry,
+      signer,
+      policy: args.trust_policy()?,
+    };
+    let scheme = if self.referrer {
+      SignatureScheme::Referrer
+    } else {
+      SignatureScheme::Tag
+    };
+    let digest = cmd.sign(&self.image, scheme)?;
+    info!(image = ?self.image, digest = ?digest, "Image signed");
+    Ok(())
+  }
+}